@@ -0,0 +1,33 @@
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::ApiContext;
+
+/// Lets a request through if it carries one of the configured bearer tokens,
+/// or if no tokens are configured at all.
+pub async fn require_token(
+    State(state): State<ApiContext>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let tokens = &state.config.auth.tokens;
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        _ if tokens.is_empty() => next.run(request).await,
+        Some(token) if tokens.iter().any(|allowed| allowed == token) => next.run(request).await,
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "Missing or unknown bearer token",
+        )
+            .into_response(),
+    }
+}
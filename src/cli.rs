@@ -0,0 +1,198 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    net::IpAddr,
+    path::PathBuf,
+};
+
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures::StreamExt;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{Result, db, export, import, routes::AlbumFilter, types::MigrationState};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Keeps track of albums scraped from rym and serves them over http"
+)]
+pub struct Cli {
+    /// TOML config file, music-rs.toml (or $MUSIC_CONFIG) by default
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve the http api and the /ui pages (what happens without a subcommand)
+    Serve(ServeArgs),
+    /// Apply the migrations the database doesn't have yet, or list them
+    Migrate {
+        #[command(subcommand)]
+        command: Option<MigrateCommand>,
+    },
+    /// Register albums from a JSON array or JSON lines file, like POST /update
+    Import {
+        file: PathBuf,
+        #[arg(long, default_value_t = import::DEFAULT_BATCH_SIZE)]
+        batch_size: usize,
+    },
+    /// Write the albums matching the filters as CSV or NDJSON
+    Export(ExportArgs),
+    /// Dump the whole catalogue with its ids, gzipped if the file ends in .gz
+    ExportSnapshot { file: PathBuf },
+    /// Restore a snapshot into a freshly migrated database
+    ImportSnapshot { file: PathBuf },
+    /// Print the same statistics as /stats
+    Stats(FilterArgs),
+    /// Credit everything of one artist to another and delete the first
+    MergeArtists { from: Uuid, into: Uuid },
+    /// Delete an album with its tracks
+    DeleteAlbum { id: Uuid },
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Command::Serve(ServeArgs::default())
+    }
+}
+
+/// Overrides for the `[server]` section of the config.
+#[derive(Args, Default)]
+pub struct ServeArgs {
+    #[arg(long)]
+    pub bind: Option<IpAddr>,
+    #[arg(long, short)]
+    pub port: Option<u16>,
+    /// Don't apply pending migrations before serving
+    #[arg(long)]
+    pub skip_migrations: bool,
+}
+
+#[derive(Subcommand, Default)]
+pub enum MigrateCommand {
+    /// Apply pending migrations (the default)
+    #[default]
+    Run,
+    /// List applied and pending migrations
+    Status,
+}
+
+/// The album filters from the query string of `/`, as flags.
+#[derive(Args)]
+pub struct FilterArgs {
+    #[arg(long, value_delimiter = ',')]
+    genres: Vec<String>,
+    #[arg(long, value_delimiter = ',')]
+    moods: Vec<String>,
+    #[arg(long, value_delimiter = ',')]
+    artists: Vec<String>,
+    #[arg(long)]
+    min_rating: Option<f64>,
+    #[arg(long)]
+    since: Option<NaiveDate>,
+    #[arg(long)]
+    to: Option<NaiveDate>,
+}
+
+impl From<FilterArgs> for AlbumFilter {
+    fn from(args: FilterArgs) -> Self {
+        AlbumFilter {
+            genres: args.genres,
+            moods: args.moods,
+            artists: args.artists,
+            min_rating: args.min_rating,
+            since: args.since,
+            to: args.to,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[arg(long, value_enum, default_value_t)]
+    format: ExportFormat,
+    /// Where to write the albums, stdout if left out
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    filters: FilterArgs,
+}
+
+pub async fn migrate(db: &PgPool, command: MigrateCommand) -> Result<()> {
+    match command {
+        MigrateCommand::Run => {
+            db::run_migrations(db).await?;
+            println!("the database is up to date");
+        }
+        MigrateCommand::Status => {
+            let statuses = db::get_migration_status(db).await?;
+            for status in &statuses {
+                println!(
+                    "{:<16} {:<8} {}",
+                    status.version,
+                    status.state.as_str(),
+                    status.description
+                );
+            }
+            let count = |state| statuses.iter().filter(|s| s.state == state).count();
+            println!(
+                "{} applied, {} pending",
+                count(MigrationState::Applied),
+                count(MigrationState::Pending)
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn export(db: PgPool, args: ExportArgs) -> Result<()> {
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    if let ExportFormat::Csv = args.format {
+        out.write_all(export::CSV_HEADER.as_bytes())?;
+    }
+    let mut albums = Box::pin(db::stream_albums(db, args.filters.into()));
+    while let Some(album) = albums.next().await {
+        let album = album?;
+        let row = match args.format {
+            ExportFormat::Csv => export::to_csv_row(&album),
+            ExportFormat::Ndjson => export::to_ndjson_row(&album)?,
+        };
+        out.write_all(row.as_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+pub async fn stats(db: &PgPool, filters: FilterArgs) -> Result<()> {
+    let stats = db::get_stats(db, &filters.into()).await?;
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+    Ok(())
+}
+
+pub async fn merge_artists(db: &PgPool, from: Uuid, into: Uuid) -> Result<()> {
+    let albums = db::merge_artists(db, from, into).await?;
+    println!("merged {from} into {into}, moving {albums} albums");
+    Ok(())
+}
+
+pub async fn delete_album(db: &PgPool, id: Uuid) -> Result<()> {
+    db::delete_album(db, id).await?;
+    println!("deleted {id}");
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    env,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::Result;
+
+/// Where the config is read from when neither `--config` nor `MUSIC_CONFIG`
+/// say otherwise. It's fine for it not to exist.
+pub const DEFAULT_CONFIG_FILE: &str = "music-rs.toml";
+
+/// Environment variables starting with this override config values, with
+/// `__` between section and key, e.g. `MUSIC_SERVER__PORT=8080`. Ones without
+/// a `__`, like `MUSIC_CONFIG` or some other tool's `MUSIC_HOME`, are left
+/// alone.
+const ENV_PREFIX: &str = "MUSIC_";
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub cache: CacheConfig,
+    pub pagination: PaginationConfig,
+    pub auth: AuthConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Apply pending migrations before serving.
+    pub migrate: bool,
+    /// How long requests in flight get to finish after SIGTERM or ctrl-c.
+    pub shutdown_timeout_secs: u64,
+    /// Where the server is reachable from outside, e.g.
+    /// `https://example.com/music`, for links back to it in feeds. Taken
+    /// from the request otherwise.
+    pub public_url: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 5000,
+            migrate: true,
+            shutdown_timeout_secs: 30,
+            public_url: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Falls back to `DATABASE_URL`, which sqlx needs at build time anyway.
+    pub url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: Option<u64>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: None,
+            max_connections: 50,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: Some(600),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn url(&self) -> Result<String> {
+        match &self.url {
+            Some(url) => Ok(url.clone()),
+            None => Ok(env::var("DATABASE_URL")?),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long cached responses live, unless `routes` says otherwise.
+    pub ttl_secs: u64,
+    /// Per route overrides, keyed by the route as written in the router,
+    /// e.g. `"/charts/{period}" = 3600`.
+    pub routes: HashMap<String, u64>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            ttl_secs: 1,
+            routes: HashMap::new(),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn ttl(&self, route: &str) -> Duration {
+        Duration::from_secs(*self.routes.get(route).unwrap_or(&self.ttl_secs))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaginationConfig {
+    pub default_limit: i64,
+    pub max_limit: i64,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        PaginationConfig {
+            default_limit: 25,
+            max_limit: 500,
+        }
+    }
+}
+
+impl PaginationConfig {
+    fn validate(&self) -> Result<()> {
+        if self.default_limit < 1 || self.max_limit < 1 {
+            return Err(
+                anyhow::anyhow!("invalid config: pagination limits have to be at least 1").into(),
+            );
+        }
+        if self.default_limit > self.max_limit {
+            return Err(anyhow::anyhow!(
+                "invalid config: pagination.default_limit ({}) is over pagination.max_limit ({})",
+                self.default_limit,
+                self.max_limit
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Bearer tokens allowed to POST to `/update`. Anyone may when empty.
+    pub tokens: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Which spans and events to log, in `RUST_LOG` syntax.
+    pub filter: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            format: LogFormat::default(),
+            filter: "music_rs=info,tower_http=info".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+impl Config {
+    /// Reads `path` if it exists, then applies `MUSIC_*` environment
+    /// overrides. Values in the environment are parsed as TOML, falling back
+    /// to plain strings, so `MUSIC_AUTH__TOKENS='["a", "b"]'` works as well as
+    /// `MUSIC_SERVER__BIND=127.0.0.1`.
+    pub fn load(path: &Path) -> Result<Config> {
+        let mut table = match std::fs::read_to_string(path) {
+            Ok(contents) => contents
+                .parse::<toml::Table>()
+                .map_err(|e| anyhow::anyhow!("can't read {}: {e}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        apply_env(&mut table, env::vars());
+
+        let config: Config = table
+            .try_into()
+            .map_err(|e| anyhow::anyhow!("invalid config: {e}"))?;
+        config.pagination.validate()?;
+        Ok(config)
+    }
+}
+
+fn apply_env(table: &mut toml::Table, vars: impl IntoIterator<Item = (String, String)>) {
+    for (key, value) in vars {
+        let Some(key) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if !key.contains("__") {
+            continue;
+        }
+        let path: Vec<String> = key.to_lowercase().split("__").map(String::from).collect();
+        insert(table, &path, parse_env_value(&value));
+    }
+}
+
+fn parse_env_value(value: &str) -> toml::Value {
+    format!("value = {value}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+fn insert(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    match path {
+        [] => {}
+        [key] => {
+            table.insert(key.clone(), value);
+        }
+        [section, rest @ ..] => {
+            let entry = table
+                .entry(section.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(section) = entry {
+                insert(section, rest, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_env(vars: &[(&str, &str)]) -> toml::Table {
+        let mut table = toml::Table::new();
+        apply_env(
+            &mut table,
+            vars.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        table
+    }
+
+    #[test]
+    fn env_overrides() {
+        let table = from_env(&[
+            ("MUSIC_SERVER__PORT", "8080"),
+            ("MUSIC_SERVER__BIND", "127.0.0.1"),
+            ("MUSIC_AUTH__TOKENS", r#"["a", "b"]"#),
+        ]);
+        let config: Config = table.try_into().unwrap();
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.bind, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(config.auth.tokens, ["a", "b"]);
+    }
+
+    #[test]
+    fn env_without_a_section_is_ignored() {
+        let table = from_env(&[
+            ("MUSIC_CONFIG", "music-rs.toml"),
+            ("MUSIC_HOME", "/home/music"),
+            ("MUSIC_DIR", "/srv/music"),
+            ("DATABASE__URL", "postgres://elsewhere"),
+        ]);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn pagination_limits() {
+        let limits = |default_limit, max_limit| {
+            PaginationConfig {
+                default_limit,
+                max_limit,
+            }
+            .validate()
+        };
+        assert!(limits(25, 500).is_ok());
+        assert!(limits(1, 1).is_ok());
+        assert!(limits(500, 500).is_ok());
+        assert!(limits(0, 500).is_err());
+        assert!(limits(25, 0).is_err());
+        assert!(limits(-1, 500).is_err());
+        assert!(limits(501, 500).is_err());
+    }
+}
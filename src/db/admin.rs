@@ -0,0 +1,75 @@
+use sqlx::{PgPool, query, query_scalar};
+use uuid::Uuid;
+
+use crate::Result;
+
+/// Moves every album credit and track feature of `from` over to `into` and
+/// deletes `from`. Returns how many albums `from` was credited on.
+#[tracing::instrument(skip(db))]
+pub async fn merge_artists(db: &PgPool, from: Uuid, into: Uuid) -> Result<u64> {
+    if from == into {
+        return Err(anyhow::anyhow!("can't merge artist {from} into itself").into());
+    }
+
+    let mut tx = db.begin().await?;
+    // Fail on unknown ids before touching anything.
+    for id in [from, into] {
+        query_scalar!("SELECT id FROM artists WHERE id = $1 FOR UPDATE", id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("there's no artist with id {id}"))?;
+    }
+    query!(
+        "INSERT INTO album_artists(artist_id, album_id)
+        SELECT $2, album_id FROM album_artists WHERE artist_id = $1
+        ON CONFLICT DO NOTHING",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await?;
+    let albums = query!("DELETE FROM album_artists WHERE artist_id = $1", from)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    let tracks = query!(
+        "UPDATE tracks SET artist = $2 WHERE artist = $1",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    query!("DELETE FROM artists WHERE id = $1", from)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    tracing::info!(albums, tracks, "merged artists");
+    Ok(albums)
+}
+
+/// Deletes an album along with its tracks and its artist, genre and mood
+/// links. The artists, genres and moods themselves stay.
+#[tracing::instrument(skip(db))]
+pub async fn delete_album(db: &PgPool, album_id: Uuid) -> Result<()> {
+    let mut tx = db.begin().await?;
+    query!("DELETE FROM album_artists WHERE album_id = $1", album_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("DELETE FROM album_genres WHERE album_id = $1", album_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("DELETE FROM album_moods WHERE album_id = $1", album_id)
+        .execute(&mut *tx)
+        .await?;
+    let deleted = query!("DELETE FROM albums WHERE id = $1", album_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(sqlx::Error::RowNotFound.into());
+    }
+    tx.commit().await?;
+    tracing::info!("deleted album");
+    Ok(())
+}
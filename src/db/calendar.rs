@@ -0,0 +1,66 @@
+use chrono::{Datelike, Days};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+use super::{
+    filters::apply_album_filter,
+    reads::{ALBUM_COLUMNS, album_from_row},
+};
+use crate::{
+    Result,
+    routes::AlbumFilter,
+    types::{Album, CalendarGroup, CalendarGrouping},
+};
+
+/// Every album matching `filters`, in the order they were released.
+pub async fn get_albums_by_release(db: &PgPool, filters: &AlbumFilter) -> Result<Vec<Album>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(
+        r#"
+        FROM albums al
+        WHERE 1=1
+        "#,
+    );
+    apply_album_filter(&mut builder, filters);
+    builder.push(" ORDER BY al.date, al.score desc");
+
+    Ok(builder
+        .build()
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(album_from_row)
+        .collect())
+}
+
+/// Every album matching `filters` in release order, grouped by the ISO week
+/// or the day it came out. Days or weeks without releases are left out.
+pub async fn get_calendar(
+    db: &PgPool,
+    grouping: CalendarGrouping,
+    filters: &AlbumFilter,
+) -> Result<Vec<CalendarGroup>> {
+    let mut groups: Vec<CalendarGroup> = Vec::new();
+    for album in get_albums_by_release(db, filters).await? {
+        let (period, start) = match grouping {
+            CalendarGrouping::Week => (
+                album.date.format("%G-W%V").to_string(),
+                album.date - Days::new(album.date.weekday().num_days_from_monday().into()),
+            ),
+            CalendarGrouping::Day => (album.date.format("%Y-%m-%d").to_string(), album.date),
+        };
+        match groups.last_mut() {
+            Some(group) if group.period == period => {
+                group.count += 1;
+                group.albums.push(album);
+            }
+            _ => groups.push(CalendarGroup {
+                period,
+                start,
+                count: 1,
+                albums: vec![album],
+            }),
+        }
+    }
+    Ok(groups)
+}
@@ -0,0 +1,70 @@
+use chrono::NaiveDate;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+
+use super::{
+    filters::{apply_album_filter, apply_limit},
+    reads::{ALBUM_COLUMNS, album_from_row},
+};
+use crate::{Result, routes::AlbumFilter, types::ChartEntry};
+
+/// Ranks albums released between `since` and `to` by a weighted rating,
+/// pulling albums with few voters towards the average score of the period.
+/// Albums with fewer than `min_voters` voters are left out entirely.
+pub async fn get_chart(
+    db: &PgPool,
+    since: NaiveDate,
+    to: NaiveDate,
+    min_voters: i32,
+    page: i64,
+    limit: i64,
+    filters: &AlbumFilter,
+) -> Result<Vec<ChartEntry>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"
+        WITH period AS (
+            SELECT AVG(score)::float8 AS mean
+            FROM albums
+            WHERE voters > 0 AND date BETWEEN "#,
+    );
+    builder.push_bind(since);
+    builder.push(" AND ");
+    builder.push_bind(to);
+    builder.push(
+        r#"
+        )
+        SELECT
+            (al.voters * al.score + "#,
+    );
+    builder.push_bind(min_voters);
+    builder.push(" * period.mean) / (al.voters + ");
+    builder.push_bind(min_voters);
+    builder.push(") AS weighted_score,");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(
+        r#"
+        FROM albums al, period
+        WHERE al.voters > 0 AND al.date BETWEEN "#,
+    );
+    builder.push_bind(since);
+    builder.push(" AND ");
+    builder.push_bind(to);
+    builder.push(" AND al.voters >= ");
+    builder.push_bind(min_voters);
+
+    apply_album_filter(&mut builder, filters);
+    builder.push(" ORDER BY weighted_score DESC, al.voters DESC");
+    apply_limit(&mut builder, page, limit);
+
+    Ok(builder
+        .build()
+        .fetch_all(db)
+        .await?
+        .iter()
+        .enumerate()
+        .map(|(i, row)| ChartEntry {
+            rank: (page - 1) * limit + i as i64 + 1,
+            weighted_score: row.get("weighted_score"),
+            album: album_from_row(row),
+        })
+        .collect())
+}
@@ -0,0 +1,50 @@
+use futures::{SinkExt, Stream, StreamExt, channel::mpsc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use tracing::{Instrument, Span};
+
+use super::{
+    filters::apply_album_filter,
+    reads::{ALBUM_COLUMNS, album_from_row},
+};
+use crate::{Result, routes::AlbumFilter, types::Album};
+
+// Rows fetched ahead of the client reading them.
+const EXPORT_BUFFER: usize = 64;
+
+/// Streams every album matching `filters` straight from the database, oldest
+/// first, without holding the whole result in memory.
+pub fn stream_albums(
+    db: PgPool,
+    filters: AlbumFilter,
+) -> impl Stream<Item = Result<Album>> + Send + 'static {
+    let (mut sender, receiver) = mpsc::channel(EXPORT_BUFFER);
+    tokio::spawn(
+        async move {
+            let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT");
+            builder.push(ALBUM_COLUMNS);
+            builder.push(
+                r#"
+            FROM albums al
+            WHERE 1=1
+            "#,
+            );
+            apply_album_filter(&mut builder, &filters);
+            builder.push(" ORDER BY al.id");
+
+            let mut rows = builder.build().fetch(&db);
+            let mut streamed = 0;
+            while let Some(row) = rows.next().await {
+                let album = row.map(|row| album_from_row(&row)).map_err(Into::into);
+                // the client hung up, no one is reading the rest
+                if sender.send(album).await.is_err() {
+                    tracing::debug!(streamed, "export cancelled");
+                    return;
+                }
+                streamed += 1;
+            }
+            tracing::debug!(streamed, "export finished");
+        }
+        .instrument(Span::current()),
+    );
+    receiver
+}
@@ -1,5 +1,16 @@
 use sqlx::{Postgres, QueryBuilder};
 
+use crate::routes::AlbumFilter;
+
+pub fn apply_album_filter<'a>(builder: &mut QueryBuilder<'a, Postgres>, filters: &'a AlbumFilter) {
+    apply_url_filter(builder, filters.url.clone());
+    apply_genre_filter(builder, &filters.genres);
+    apply_mood_filter(builder, &filters.moods);
+    apply_artist_filter(builder, &filters.artists);
+    apply_rating_filter(builder, filters.min_rating);
+    apply_date_range_filter(builder, filters.since, filters.to);
+}
+
 pub fn apply_genre_filter<'a>(builder: &mut QueryBuilder<'a, Postgres>, genres: &'a [String]) {
     if !genres.is_empty() {
         builder.push(" AND (SELECT COUNT(DISTINCT g2.name) FROM album_genres ag2 JOIN genres g2 ON ag2.genre_id = g2.id WHERE ag2.album_id = al.id AND g2.name = ANY(");
@@ -18,6 +29,15 @@ pub fn apply_mood_filter<'a>(builder: &mut QueryBuilder<'a, Postgres>, moods: &'
     }
 }
 
+pub fn apply_artist_filter<'a>(builder: &mut QueryBuilder<'a, Postgres>, artists: &'a [String]) {
+    if !artists.is_empty() {
+        builder.push(" AND (SELECT COUNT(DISTINCT ar2.name) FROM album_artists aa2 JOIN artists ar2 ON aa2.artist_id = ar2.id WHERE aa2.album_id = al.id AND ar2.name = ANY(");
+        builder.push_bind(artists);
+        builder.push(")) = ");
+        builder.push_bind(artists.len() as i64);
+    }
+}
+
 pub fn apply_rating_filter(builder: &mut QueryBuilder<Postgres>, min_rating: Option<f64>) {
     if let Some(min_rating) = min_rating {
         builder.push(" AND al.score >= ");
@@ -48,7 +68,12 @@ pub fn apply_url_filter(builder: &mut QueryBuilder<Postgres>, url: Option<String
 }
 
 pub fn apply_pagination(builder: &mut QueryBuilder<Postgres>, page: i64, limit: i64) {
-    builder.push(" ORDER BY al.date desc, al.score desc LIMIT ");
+    builder.push(" ORDER BY al.date desc, al.score desc");
+    apply_limit(builder, page, limit);
+}
+
+pub fn apply_limit(builder: &mut QueryBuilder<Postgres>, page: i64, limit: i64) {
+    builder.push(" LIMIT ");
     builder.push_bind(limit);
     builder.push(" OFFSET ");
     builder.push_bind((page - 1) * limit);
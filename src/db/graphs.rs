@@ -0,0 +1,89 @@
+use sqlx::PgPool;
+
+use super::stats::{TagKind, filtered_albums};
+use crate::{Result, routes::AlbumFilter, types::Graph};
+
+/// Every artist credited on an album, either as an album artist or on a track.
+const CREDITS: &str = r#"(
+    SELECT artist_id, album_id FROM album_artists
+    UNION
+    SELECT artist, album_id FROM tracks WHERE artist IS NOT NULL
+)"#;
+
+pub async fn get_genre_graph(db: &PgPool, min_weight: i64, filters: &AlbumFilter) -> Result<Graph> {
+    get_tag_graph(db, TagKind::Genre, min_weight, filters).await
+}
+
+pub async fn get_mood_graph(db: &PgPool, min_weight: i64, filters: &AlbumFilter) -> Result<Graph> {
+    get_tag_graph(db, TagKind::Mood, min_weight, filters).await
+}
+
+/// Tags as nodes sized by their album count, linked by how many albums they
+/// share. Links sharing fewer than `min_weight` albums are left out, and so
+/// are tags with fewer albums than that, as they can't have any links left.
+async fn get_tag_graph(
+    db: &PgPool,
+    kind: TagKind,
+    min_weight: i64,
+    filters: &AlbumFilter,
+) -> Result<Graph> {
+    let mut builder = filtered_albums(
+        "SELECT tag.id, tag.name, COUNT(1) AS size",
+        kind.join(),
+        filters,
+    );
+    builder.push(" GROUP BY tag.id HAVING COUNT(1) >= ");
+    builder.push_bind(min_weight);
+    builder.push(" ORDER BY size DESC, tag.name");
+    let nodes = builder.build_query_as().fetch_all(db).await?;
+
+    let mut builder = filtered_albums(
+        "SELECT pairs.source, pairs.target, COUNT(1) AS weight",
+        kind.pair_join(),
+        filters,
+    );
+    builder.push(" GROUP BY pairs.source, pairs.target HAVING COUNT(1) >= ");
+    builder.push_bind(min_weight);
+    builder.push(" ORDER BY weight DESC");
+    let links = builder.build_query_as().fetch_all(db).await?;
+
+    Ok(Graph { nodes, links })
+}
+
+/// Artists as nodes sized by their release count, linked by how many releases
+/// they share through album or track credits. Like the tag graphs, links and
+/// artists with fewer than `min_weight` releases are left out.
+pub async fn get_artist_graph(
+    db: &PgPool,
+    min_weight: i64,
+    filters: &AlbumFilter,
+) -> Result<Graph> {
+    let mut builder = filtered_albums(
+        "SELECT ar.id, ar.name, COUNT(1) AS size",
+        &format!(
+            "JOIN {CREDITS} credits ON credits.album_id = al.id JOIN artists ar ON ar.id = credits.artist_id"
+        ),
+        filters,
+    );
+    builder.push(" GROUP BY ar.id HAVING COUNT(1) >= ");
+    builder.push_bind(min_weight);
+    builder.push(" ORDER BY size DESC, ar.name");
+    let nodes = builder.build_query_as().fetch_all(db).await?;
+
+    let mut builder = filtered_albums(
+        "SELECT pairs.source, pairs.target, COUNT(1) AS weight",
+        &format!(
+            r#"JOIN (SELECT a.album_id, a.artist_id AS source, b.artist_id AS target
+                FROM {CREDITS} a
+                JOIN {CREDITS} b ON b.album_id = a.album_id AND a.artist_id < b.artist_id
+            ) pairs ON pairs.album_id = al.id"#
+        ),
+        filters,
+    );
+    builder.push(" GROUP BY pairs.source, pairs.target HAVING COUNT(1) >= ");
+    builder.push_bind(min_weight);
+    builder.push(" ORDER BY weight DESC");
+    let links = builder.build_query_as().fetch_all(db).await?;
+
+    Ok(Graph { nodes, links })
+}
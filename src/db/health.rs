@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use sqlx::{PgPool, query};
+
+use super::migrations::get_migration_status;
+use crate::{
+    Result,
+    types::{MigrationState, Readiness},
+};
+
+/// How long the database gets to answer before we call it unreachable,
+/// rather than waiting out the pool's acquire timeout.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the database answers and has every migration this binary knows,
+/// unchanged.
+pub async fn get_readiness(db: &PgPool) -> Readiness {
+    match tokio::time::timeout(READINESS_TIMEOUT, check(db)).await {
+        Ok(Ok((pending_migrations, changed_migrations))) => Readiness {
+            ready: pending_migrations.is_empty() && changed_migrations.is_empty(),
+            database_error: None,
+            pending_migrations,
+            changed_migrations,
+        },
+        Ok(Err(error)) => unreachable(error.to_string()),
+        Err(_) => unreachable(format!(
+            "no answer within {} seconds",
+            READINESS_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+/// The versions of the pending and the changed migrations.
+async fn check(db: &PgPool) -> Result<(Vec<i64>, Vec<i64>)> {
+    query!("SELECT 1 as one").fetch_one(db).await?;
+    let statuses = get_migration_status(db).await?;
+    let versions = |state| {
+        statuses
+            .iter()
+            .filter(|status| status.state == state)
+            .map(|status| status.version)
+            .collect()
+    };
+    Ok((
+        versions(MigrationState::Pending),
+        versions(MigrationState::Changed),
+    ))
+}
+
+fn unreachable(error: String) -> Readiness {
+    Readiness {
+        ready: false,
+        database_error: Some(error),
+        pending_migrations: Vec::new(),
+        changed_migrations: Vec::new(),
+    }
+}
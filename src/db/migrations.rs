@@ -0,0 +1,110 @@
+use std::{collections::HashMap, time::Duration};
+
+use sqlx::{
+    PgPool,
+    migrate::{MigrateError, Migrator},
+};
+
+use crate::{
+    Result,
+    types::{MigrationState, MigrationStatus},
+};
+
+/// The `migrations/` directory, compiled into the binary.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// How long to wait between attempts while the database can't be reached.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+pub async fn run_migrations(db: &PgPool) -> Result<()> {
+    MIGRATOR.run(db).await?;
+    log_applied();
+    Ok(())
+}
+
+/// Like [`run_migrations`], but keeps trying for as long as the database
+/// can't be reached, e.g. when it's still starting up. Migrations that fail
+/// on their own, like one with broken SQL or one changed since it was
+/// applied, still fail right away.
+pub async fn run_migrations_when_reachable(db: &PgPool) -> Result<()> {
+    loop {
+        match MIGRATOR.run(db).await {
+            Ok(()) => {
+                log_applied();
+                return Ok(());
+            }
+            Err(error) if is_unreachable(&error) => {
+                tracing::warn!(
+                    %error,
+                    retry_secs = RETRY_INTERVAL.as_secs(),
+                    "can't reach the database to migrate it, retrying"
+                );
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+fn log_applied() {
+    tracing::info!(
+        latest = MIGRATOR.iter().map(|migration| migration.version).max(),
+        "migrations applied"
+    );
+}
+
+/// Whether `error` is about getting to the database rather than about the
+/// migrations.
+fn is_unreachable(error: &MigrateError) -> bool {
+    let MigrateError::Execute(error) = error else {
+        return false;
+    };
+    match error {
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut => true,
+        // connection_exception, or the server starting up or shutting down.
+        sqlx::Error::Database(error) => error
+            .code()
+            .is_some_and(|code| code.starts_with("08") || code.starts_with("57P")),
+        _ => false,
+    }
+}
+
+/// Every migration the binary knows about or the database has applied, by
+/// version. Only reads, so it's fine to call from a health check with a
+/// read-only role. A database without the migrations table has everything
+/// pending.
+pub async fn get_migration_status(db: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let has_table: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(db)
+        .await?;
+    let mut applied: HashMap<i64, Vec<u8>> = if has_table {
+        sqlx::query_as("SELECT version, checksum FROM _sqlx_migrations WHERE success")
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    let mut statuses: Vec<MigrationStatus> = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            state: match applied.remove(&migration.version) {
+                Some(checksum) if *checksum == *migration.checksum => MigrationState::Applied,
+                Some(_) => MigrationState::Changed,
+                None => MigrationState::Pending,
+            },
+        })
+        .collect();
+    statuses.extend(applied.into_keys().map(|version| MigrationStatus {
+        version,
+        description: String::new(),
+        state: MigrationState::Unknown,
+    }));
+    statuses.sort_by_key(|status| status.version);
+    Ok(statuses)
+}
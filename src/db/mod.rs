@@ -1,10 +1,33 @@
+mod admin;
+mod calendar;
+mod charts;
+mod export;
 mod filters;
+mod graphs;
+mod health;
+mod migrations;
 mod reads;
+mod similar;
+mod snapshot;
+mod stats;
+mod trends;
 mod writes;
 
 // Re-export public API
+pub use admin::{delete_album, merge_artists};
+pub use calendar::{get_albums_by_release, get_calendar};
+pub use charts::get_chart;
+pub use export::stream_albums;
+pub use graphs::{get_artist_graph, get_genre_graph, get_mood_graph};
+pub use health::get_readiness;
+pub use migrations::{get_migration_status, run_migrations, run_migrations_when_reachable};
 pub use reads::{
-    get_albums, get_albums_for_artist, get_albums_for_genre, get_albums_for_mood, get_genre,
-    get_mood, get_similar_genres, get_similar_moods,
+    get_album, get_albums, get_albums_for_artist, get_albums_for_genre, get_albums_for_mood,
+    get_artist, get_genre, get_hidden_gems, get_mood, get_random_albums, get_recent_albums,
+    get_similar_genres, get_similar_moods,
 };
+pub use similar::{get_similar_albums, get_similar_artists};
+pub use snapshot::{get_snapshot, restore_snapshot};
+pub use stats::get_stats;
+pub use trends::{get_genre_trend, get_growing_genres, get_growing_moods, get_mood_trend};
 pub use writes::register_album;
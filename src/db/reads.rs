@@ -1,25 +1,18 @@
-use sqlx::{PgPool, Postgres, QueryBuilder, Row, query_as};
+use sqlx::{PgPool, Postgres, QueryBuilder, Row, postgres::PgRow, query_as};
 use uuid::Uuid;
 
-use super::filters::{
-    apply_date_range_filter, apply_genre_filter, apply_mood_filter, apply_pagination,
-    apply_rating_filter,
-};
+use super::filters::{apply_album_filter, apply_limit, apply_pagination};
 use crate::{
     Result,
-    db::filters::apply_url_filter,
-    types::{Album, Artist, Genre, Mood, SimilarGenre, SimilarMood, Track},
+    routes::AlbumFilter,
+    types::{
+        Album, Artist, FeedOrder, Genre, Mood, SimilarGenre, SimilarMood, SimilarityMetric, Track,
+    },
 };
 
-pub async fn get_albums(
-    db: &PgPool,
-    page: i64,
-    limit: i64,
-    filters: &crate::routes::AlbumFilter,
-) -> Result<Vec<Album>> {
-    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
-        r#"
-        SELECT 
+/// Album columns with artists, genres, moods and tracks aggregated as json,
+/// selected from an `albums` table aliased as `al`.
+pub(super) const ALBUM_COLUMNS: &str = r#"
             al.id, al.title, al.localized_title, al.date, al.url, al.rym_url, al.score, al.voters, al.modified_date,
             COALESCE((SELECT json_agg(DISTINCT jsonb_build_object('id', ar.id, 'name', ar.name, 'localized_name', ar.localized_name))
                       FROM album_artists aa
@@ -38,47 +31,161 @@ pub async fn get_albums(
                       FROM tracks t
                       LEFT JOIN artists ta ON t.artist = ta.id
                       WHERE t.album_id = al.id), '[]') as tracks
+"#;
+
+pub(super) fn album_from_row(row: &PgRow) -> Album {
+    use sqlx::types::JsonValue;
+
+    let artists_json: JsonValue = row.get("artists");
+    let genres_json: JsonValue = row.get("genres");
+    let moods_json: JsonValue = row.get("moods");
+    let tracks_json: JsonValue = row.get("tracks");
+
+    Album {
+        id: row.get("id"),
+        title: row.get("title"),
+        localized_title: row.get("localized_title"),
+        date: row.get("date"),
+        url: row.get("url"),
+        rym_url: row.get("rym_url"),
+        score: row.get("score"),
+        voters: row.get("voters"),
+        modified_date: row.get("modified_date"),
+        artists: serde_json::from_value(artists_json).ok(),
+        genres: serde_json::from_value(genres_json).ok(),
+        moods: serde_json::from_value(moods_json).ok(),
+        tracks: serde_json::from_value(tracks_json).ok(),
+    }
+}
+
+#[tracing::instrument(skip(db, filters))]
+pub async fn get_albums(
+    db: &PgPool,
+    page: i64,
+    limit: i64,
+    filters: &AlbumFilter,
+) -> Result<Vec<Album>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(
+        r#"
         FROM albums al
         WHERE 1=1
         "#,
     );
 
-    apply_url_filter(&mut builder, filters.url.clone());
-    apply_genre_filter(&mut builder, &filters.genres);
-    apply_mood_filter(&mut builder, &filters.moods);
-    apply_rating_filter(&mut builder, filters.min_rating);
-    apply_date_range_filter(&mut builder, filters.since, filters.to);
+    apply_album_filter(&mut builder, filters);
     apply_pagination(&mut builder, page, limit);
 
-    let query = builder.build();
-    Ok(query
+    let rows = builder.build().fetch_all(db).await?;
+    tracing::debug!(rows = rows.len(), "fetched albums");
+    Ok(rows.iter().map(album_from_row).collect())
+}
+
+/// Picks random albums matching `filters`. With a `seed` the order is
+/// stable, so the same seed gives the same picks and pages through them.
+pub async fn get_random_albums(
+    db: &PgPool,
+    page: i64,
+    limit: i64,
+    seed: Option<&str>,
+    filters: &AlbumFilter,
+) -> Result<Vec<Album>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(
+        r#"
+        FROM albums al
+        WHERE 1=1
+        "#,
+    );
+
+    apply_album_filter(&mut builder, filters);
+    match seed {
+        Some(seed) => {
+            builder.push(" ORDER BY md5(al.id::text || ");
+            builder.push_bind(seed.to_string());
+            builder.push(")");
+        }
+        None => {
+            builder.push(" ORDER BY random()");
+        }
+    }
+    apply_limit(&mut builder, page, limit);
+
+    Ok(builder
+        .build()
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(album_from_row)
+        .collect())
+}
+
+/// The most recently modified or created albums matching `filters`.
+pub async fn get_recent_albums(
+    db: &PgPool,
+    page: i64,
+    limit: i64,
+    order: FeedOrder,
+    filters: &AlbumFilter,
+) -> Result<Vec<Album>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(
+        r#"
+        FROM albums al
+        WHERE 1=1
+        "#,
+    );
+
+    apply_album_filter(&mut builder, filters);
+    match order {
+        FeedOrder::Modified => builder.push(" ORDER BY al.modified_date desc, al.id desc"),
+        // uuidv7 ids sort by the time they were created
+        FeedOrder::Created => builder.push(" ORDER BY al.id desc"),
+    };
+    apply_limit(&mut builder, page, limit);
+
+    Ok(builder
+        .build()
         .fetch_all(db)
         .await?
-        .into_iter()
-        .map(|row| {
-            use sqlx::types::JsonValue;
+        .iter()
+        .map(album_from_row)
+        .collect())
+}
+
+/// Well rated albums that few people have voted on yet, best rated first.
+pub async fn get_hidden_gems(
+    db: &PgPool,
+    page: i64,
+    limit: i64,
+    min_voters: i32,
+    max_voters: i32,
+    filters: &AlbumFilter,
+) -> Result<Vec<Album>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(
+        r#"
+        FROM albums al
+        WHERE al.voters >= "#,
+    );
+    builder.push_bind(min_voters);
+    builder.push(" AND al.voters <= ");
+    builder.push_bind(max_voters);
 
-            let artists_json: JsonValue = row.get("artists");
-            let genres_json: JsonValue = row.get("genres");
-            let moods_json: JsonValue = row.get("moods");
-            let tracks_json: JsonValue = row.get("tracks");
+    apply_album_filter(&mut builder, filters);
+    builder.push(" ORDER BY al.score desc, al.voters desc");
+    apply_limit(&mut builder, page, limit);
 
-            Album {
-                id: row.get("id"),
-                title: row.get("title"),
-                localized_title: row.get("localized_title"),
-                date: row.get("date"),
-                url: row.get("url"),
-                rym_url: row.get("rym_url"),
-                score: row.get("score"),
-                voters: row.get("voters"),
-                modified_date: row.get("modified_date"),
-                artists: serde_json::from_value(artists_json).ok(),
-                genres: serde_json::from_value(genres_json).ok(),
-                moods: serde_json::from_value(moods_json).ok(),
-                tracks: serde_json::from_value(tracks_json).ok(),
-            }
-        })
+    Ok(builder
+        .build()
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(album_from_row)
         .collect())
 }
 
@@ -217,6 +324,21 @@ pub async fn get_albums_for_mood(
     .await?)
 }
 
+pub async fn get_album(db: &PgPool, album_id: Uuid) -> Result<Album> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(" FROM albums al WHERE al.id = ");
+    builder.push_bind(album_id);
+    Ok(album_from_row(&builder.build().fetch_one(db).await?))
+}
+
+pub async fn get_artist(db: &PgPool, artist_id: Uuid) -> Result<Artist> {
+    let artist = query_as!(Artist, "SELECT * FROM artists WHERE id = $1", artist_id)
+        .fetch_one(db)
+        .await?;
+    Ok(artist)
+}
+
 pub async fn get_genre(db: &PgPool, genre_id: Uuid) -> Result<Genre> {
     let genre = query_as!(Genre, "SELECT * FROM genres WHERE id = $1", genre_id)
         .fetch_one(db)
@@ -231,46 +353,98 @@ pub async fn get_mood(db: &PgPool, mood_id: Uuid) -> Result<Mood> {
     Ok(mood)
 }
 
-pub async fn get_similar_genres(db: &PgPool, genre_id: Uuid) -> Result<Vec<SimilarGenre>> {
+pub async fn get_similar_genres(
+    db: &PgPool,
+    genre_id: Uuid,
+    metric: SimilarityMetric,
+    min_support: i64,
+) -> Result<Vec<SimilarGenre>> {
     let album_genres: Vec<SimilarGenre> = query_as!(
         SimilarGenre,
         r#"SELECT
-            related_genre_details.id AS id,
-            related_genre_details.name AS name,
-            COUNT(1) AS count
-        FROM album_genres AS related_albums
-        INNER JOIN album_genres AS related_genres
-            ON related_albums.album_id = related_genres.album_id
-        INNER JOIN genres AS related_genre_details
-            ON related_genres.genre_id = related_genre_details.id
-        WHERE related_albums.genre_id = $1
-        GROUP BY related_genre_details.id
-        ORDER BY count DESC
+            id,
+            name,
+            count,
+            CASE $2
+                WHEN 'jaccard' THEN count::float8 / (target_total + related_total - count)
+                WHEN 'pmi' THEN LN(count::float8 * total / (target_total * related_total))
+                WHEN 'lift' THEN count::float8 * total / (target_total * related_total)
+                ELSE count::float8
+            END AS score
+        FROM (
+            SELECT
+                related_genre_details.id AS id,
+                related_genre_details.name AS name,
+                COUNT(1) AS count,
+                related_totals.total::float8 AS related_total,
+                (SELECT COUNT(1) FROM album_genres WHERE genre_id = $1)::float8 AS target_total,
+                (SELECT COUNT(DISTINCT album_id) FROM album_genres)::float8 AS total
+            FROM album_genres AS related_albums
+            INNER JOIN album_genres AS related_genres
+                ON related_albums.album_id = related_genres.album_id
+                AND related_genres.genre_id != related_albums.genre_id
+            INNER JOIN genres AS related_genre_details
+                ON related_genres.genre_id = related_genre_details.id
+            INNER JOIN (SELECT genre_id, COUNT(1) AS total FROM album_genres GROUP BY genre_id) AS related_totals
+                ON related_totals.genre_id = related_genre_details.id
+            WHERE related_albums.genre_id = $1
+            GROUP BY related_genre_details.id, related_totals.total
+            HAVING COUNT(1) >= $3
+        ) AS co_occurrences
+        ORDER BY score DESC, count DESC
         "#,
-        genre_id
+        genre_id,
+        metric.as_str(),
+        min_support
     )
     .fetch_all(db)
     .await?;
     Ok(album_genres)
 }
 
-pub async fn get_similar_moods(db: &PgPool, mood_id: Uuid) -> Result<Vec<SimilarMood>> {
+pub async fn get_similar_moods(
+    db: &PgPool,
+    mood_id: Uuid,
+    metric: SimilarityMetric,
+    min_support: i64,
+) -> Result<Vec<SimilarMood>> {
     let album_moods: Vec<SimilarMood> = query_as!(
         SimilarMood,
         r#"SELECT
-            related_mood_details.id AS id,
-            related_mood_details.name AS name,
-            COUNT(1) AS count
-        FROM album_moods AS related_albums
-        INNER JOIN album_moods AS related_moods
-            ON related_albums.album_id = related_moods.album_id
-        INNER JOIN moods AS related_mood_details
-            ON related_moods.mood_id = related_mood_details.id
-        WHERE related_albums.mood_id = $1
-        GROUP BY related_mood_details.id
-        ORDER BY count DESC
+            id,
+            name,
+            count,
+            CASE $2
+                WHEN 'jaccard' THEN count::float8 / (target_total + related_total - count)
+                WHEN 'pmi' THEN LN(count::float8 * total / (target_total * related_total))
+                WHEN 'lift' THEN count::float8 * total / (target_total * related_total)
+                ELSE count::float8
+            END AS score
+        FROM (
+            SELECT
+                related_mood_details.id AS id,
+                related_mood_details.name AS name,
+                COUNT(1) AS count,
+                related_totals.total::float8 AS related_total,
+                (SELECT COUNT(1) FROM album_moods WHERE mood_id = $1)::float8 AS target_total,
+                (SELECT COUNT(DISTINCT album_id) FROM album_moods)::float8 AS total
+            FROM album_moods AS related_albums
+            INNER JOIN album_moods AS related_moods
+                ON related_albums.album_id = related_moods.album_id
+                AND related_moods.mood_id != related_albums.mood_id
+            INNER JOIN moods AS related_mood_details
+                ON related_moods.mood_id = related_mood_details.id
+            INNER JOIN (SELECT mood_id, COUNT(1) AS total FROM album_moods GROUP BY mood_id) AS related_totals
+                ON related_totals.mood_id = related_mood_details.id
+            WHERE related_albums.mood_id = $1
+            GROUP BY related_mood_details.id, related_totals.total
+            HAVING COUNT(1) >= $3
+        ) AS co_occurrences
+        ORDER BY score DESC, count DESC
         "#,
-        mood_id
+        mood_id,
+        metric.as_str(),
+        min_support
     )
     .fetch_all(db)
     .await?;
@@ -0,0 +1,188 @@
+use sqlx::{PgPool, Postgres, QueryBuilder, Row, query_as};
+use uuid::Uuid;
+
+use super::{
+    filters::{apply_album_filter, apply_limit},
+    reads::{ALBUM_COLUMNS, album_from_row},
+};
+use crate::{
+    Result,
+    routes::{AlbumFilter, SimilarAlbumsFilter},
+    types::{SimilarAlbum, SimilarArtist},
+};
+
+// How much a shared tag of each kind counts towards album similarity,
+// before it's scaled by how rare the tag is.
+const GENRE_WEIGHT: f64 = 1.0;
+const MOOD_WEIGHT: f64 = 0.5;
+const ARTIST_WEIGHT: f64 = 2.0;
+
+// How much each shared release (log scaled) adds on top of the tag profile
+// similarity between two artists.
+const COLLABORATION_WEIGHT: f64 = 0.5;
+
+/// Ranks other albums by the genres, moods and artists they share with
+/// `album_id`, each shared tag weighted by its inverse document frequency.
+///
+/// `score_weight` boosts well rated albums and `recency_weight` boosts
+/// recent releases, both are off at 0.
+pub async fn get_similar_albums(
+    db: &PgPool,
+    album_id: Uuid,
+    page: i64,
+    limit: i64,
+    filters: &AlbumFilter,
+    weights: &SimilarAlbumsFilter,
+) -> Result<Vec<SimilarAlbum>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"
+        WITH tags AS (
+            SELECT 'genre' AS kind, genre_id AS tag_id, album_id FROM album_genres
+            UNION ALL
+            SELECT 'mood', mood_id, album_id FROM album_moods
+            UNION ALL
+            SELECT 'artist', artist_id, album_id FROM album_artists
+        ),
+        idf AS (
+            SELECT kind, tag_id, LN((SELECT COUNT(1) FROM albums)::float8 / COUNT(1)) AS weight
+            FROM tags
+            GROUP BY kind, tag_id
+        ),
+        shared AS (
+            SELECT
+                other.album_id,
+                SUM(idf.weight * CASE idf.kind
+                    WHEN 'genre' THEN "#,
+    );
+    builder.push_bind(GENRE_WEIGHT);
+    builder.push(" WHEN 'mood' THEN ");
+    builder.push_bind(MOOD_WEIGHT);
+    builder.push(" ELSE ");
+    builder.push_bind(ARTIST_WEIGHT);
+    builder.push(
+        r#" END) AS similarity
+            FROM tags mine
+            JOIN tags other
+                ON other.kind = mine.kind
+                AND other.tag_id = mine.tag_id
+                AND other.album_id != mine.album_id
+            JOIN idf
+                ON idf.kind = mine.kind
+                AND idf.tag_id = mine.tag_id
+            WHERE mine.album_id = "#,
+    );
+    builder.push_bind(album_id);
+    builder.push(
+        r#"
+            GROUP BY other.album_id
+        )
+        SELECT
+            shared.similarity
+                * (1 + "#,
+    );
+    builder.push_bind(weights.score_weight.unwrap_or(0.0));
+    builder.push(" * al.score / 5) * (1 + ");
+    builder.push_bind(weights.recency_weight.unwrap_or(0.0));
+    builder.push(" * EXP(LEAST(al.date - CURRENT_DATE, 0) / 365.0)) AS similarity,");
+    builder.push(ALBUM_COLUMNS);
+    builder.push(
+        r#"
+        FROM albums al
+        JOIN shared ON shared.album_id = al.id
+        WHERE 1=1
+        "#,
+    );
+
+    apply_album_filter(&mut builder, filters);
+    builder.push(" ORDER BY similarity DESC, al.score DESC");
+    apply_limit(&mut builder, page, limit);
+
+    Ok(builder
+        .build()
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| SimilarAlbum {
+            album: album_from_row(row),
+            similarity: row.get("similarity"),
+        })
+        .collect())
+}
+
+/// Ranks other artists by the cosine similarity of their genre and mood
+/// profiles to `artist_id`, plus a bonus for every release they share,
+/// either as album artists or through track credits.
+pub async fn get_similar_artists(
+    db: &PgPool,
+    artist_id: Uuid,
+    page: i64,
+    limit: i64,
+) -> Result<Vec<SimilarArtist>> {
+    Ok(query_as!(
+        SimilarArtist,
+        r#"
+        WITH artist_albums AS (
+            SELECT artist_id, album_id FROM album_artists
+            UNION
+            SELECT artist, album_id FROM tracks WHERE artist IS NOT NULL
+        ),
+        profiles AS (
+            SELECT aa.artist_id, 'genre' AS kind, ag.genre_id AS tag_id, COUNT(1)::float8 AS weight
+            FROM artist_albums aa
+            JOIN album_genres ag ON ag.album_id = aa.album_id
+            GROUP BY aa.artist_id, ag.genre_id
+            UNION ALL
+            SELECT aa.artist_id, 'mood', am.mood_id, COUNT(1)::float8
+            FROM artist_albums aa
+            JOIN album_moods am ON am.album_id = aa.album_id
+            GROUP BY aa.artist_id, am.mood_id
+        ),
+        norms AS (
+            SELECT artist_id, SQRT(SUM(weight * weight)) AS norm
+            FROM profiles
+            GROUP BY artist_id
+        ),
+        tag_similarity AS (
+            SELECT
+                other.artist_id,
+                SUM(mine.weight * other.weight) / (mine_norm.norm * other_norm.norm) AS similarity
+            FROM profiles mine
+            JOIN profiles other
+                ON other.kind = mine.kind
+                AND other.tag_id = mine.tag_id
+                AND other.artist_id != mine.artist_id
+            JOIN norms mine_norm ON mine_norm.artist_id = mine.artist_id
+            JOIN norms other_norm ON other_norm.artist_id = other.artist_id
+            WHERE mine.artist_id = $1
+            GROUP BY other.artist_id, mine_norm.norm, other_norm.norm
+        ),
+        collaborations AS (
+            SELECT other.artist_id, COUNT(DISTINCT other.album_id) AS shared
+            FROM artist_albums mine
+            JOIN artist_albums other
+                ON other.album_id = mine.album_id
+                AND other.artist_id != mine.artist_id
+            WHERE mine.artist_id = $1
+            GROUP BY other.artist_id
+        )
+        SELECT
+            ar.id,
+            ar.name,
+            ar.localized_name,
+            COALESCE(ts.similarity, 0) + $2 * LN(1 + COALESCE(c.shared, 0)) AS "similarity!",
+            COALESCE(c.shared, 0) AS "shared_releases!"
+        FROM artists ar
+        LEFT JOIN tag_similarity ts ON ts.artist_id = ar.id
+        LEFT JOIN collaborations c ON c.artist_id = ar.id
+        WHERE ts.artist_id IS NOT NULL OR c.artist_id IS NOT NULL
+        ORDER BY 4 DESC, ar.name
+        LIMIT $3
+        OFFSET $4"#,
+        artist_id,
+        COLLABORATION_WEIGHT,
+        limit,
+        (page - 1) * limit
+    )
+    .fetch_all(db)
+    .await?)
+}
@@ -0,0 +1,227 @@
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction, query, query_as, query_scalar};
+
+use crate::{
+    Result,
+    types::{
+        AlbumArtist, AlbumGenre, AlbumMood, Artist, Genre, Mood, Snapshot, SnapshotAlbum,
+        SnapshotTrack,
+    },
+};
+
+pub const SNAPSHOT_FORMAT: &str = "music-rs-snapshot";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Postgres takes at most 65535 bind parameters per statement, so rows are
+/// inserted this many at a time.
+const INSERT_BATCH: usize = 1000;
+
+/// Reads every table of the catalogue, in an order that can be inserted
+/// back as is. All tables are read from one snapshot of the database, so
+/// albums registered meanwhile can't leave links pointing nowhere.
+pub async fn get_snapshot(db: &PgPool) -> Result<Snapshot> {
+    let mut tx = db.begin().await?;
+    query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let artists = query_as!(
+        Artist,
+        "SELECT id, name, localized_name FROM artists ORDER BY id"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let genres = query_as!(Genre, "SELECT id, name FROM genres ORDER BY id")
+        .fetch_all(&mut *tx)
+        .await?;
+    let moods = query_as!(Mood, "SELECT id, name FROM moods ORDER BY id")
+        .fetch_all(&mut *tx)
+        .await?;
+    let albums = query_as!(
+        SnapshotAlbum,
+        "SELECT id, title, localized_title, date, url, rym_url, score, voters, modified_date
+        FROM albums ORDER BY id"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let album_artists = query_as!(
+        AlbumArtist,
+        r#"SELECT album_id as "album_id!", artist_id as "artist_id!"
+        FROM album_artists ORDER BY album_id, artist_id"#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let album_genres = query_as!(
+        AlbumGenre,
+        r#"SELECT album_id as "album_id!", genre_id as "genre_id!"
+        FROM album_genres ORDER BY album_id, genre_id"#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let album_moods = query_as!(
+        AlbumMood,
+        r#"SELECT album_id as "album_id!", mood_id as "mood_id!"
+        FROM album_moods ORDER BY album_id, mood_id"#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let tracks = query_as!(
+        SnapshotTrack,
+        "SELECT id, album_id, track_number, title, localized_title, artist
+        FROM tracks ORDER BY album_id, track_number"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Snapshot {
+        format: SNAPSHOT_FORMAT.to_string(),
+        version: SNAPSHOT_VERSION,
+        created: chrono::Utc::now(),
+        artists,
+        genres,
+        moods,
+        albums,
+        album_artists,
+        album_genres,
+        album_moods,
+        tracks,
+    })
+}
+
+/// Inserts everything in `snapshot` in one transaction. Only an empty
+/// catalogue is restored into, so ids and names can't clash with what's
+/// already there.
+pub async fn restore_snapshot(db: &PgPool, snapshot: &Snapshot) -> Result<()> {
+    if snapshot.format != SNAPSHOT_FORMAT || snapshot.version != SNAPSHOT_VERSION {
+        return Err(anyhow::anyhow!(
+            "can't restore a {} version {} snapshot, expected {SNAPSHOT_FORMAT} version {SNAPSHOT_VERSION}",
+            snapshot.format,
+            snapshot.version
+        )
+        .into());
+    }
+
+    let mut tx = db.begin().await?;
+    let existing: i64 = query_scalar!(
+        r#"SELECT (SELECT COUNT(1) FROM albums) + (SELECT COUNT(1) FROM artists)
+        + (SELECT COUNT(1) FROM genres) + (SELECT COUNT(1) FROM moods) as "count!""#
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if existing > 0 {
+        return Err(anyhow::anyhow!(
+            "the database already has {existing} albums, artists, genres or moods, restore into a freshly migrated one"
+        )
+        .into());
+    }
+
+    insert_rows(
+        &mut tx,
+        "INSERT INTO artists(id, name, localized_name) ",
+        &snapshot.artists,
+        |mut row, artist| {
+            row.push_bind(artist.id)
+                .push_bind(&artist.name)
+                .push_bind(&artist.localized_name);
+        },
+    )
+    .await?;
+    insert_rows(
+        &mut tx,
+        "INSERT INTO genres(id, name) ",
+        &snapshot.genres,
+        |mut row, genre| {
+            row.push_bind(genre.id).push_bind(&genre.name);
+        },
+    )
+    .await?;
+    insert_rows(
+        &mut tx,
+        "INSERT INTO moods(id, name) ",
+        &snapshot.moods,
+        |mut row, mood| {
+            row.push_bind(mood.id).push_bind(&mood.name);
+        },
+    )
+    .await?;
+    insert_rows(
+        &mut tx,
+        "INSERT INTO albums(id, title, localized_title, date, url, rym_url, score, voters, modified_date) ",
+        &snapshot.albums,
+        |mut row, album| {
+            row.push_bind(album.id)
+                .push_bind(&album.title)
+                .push_bind(&album.localized_title)
+                .push_bind(album.date)
+                .push_bind(&album.url)
+                .push_bind(&album.rym_url)
+                .push_bind(album.score)
+                .push_bind(album.voters)
+                .push_bind(album.modified_date);
+        },
+    )
+    .await?;
+    insert_rows(
+        &mut tx,
+        "INSERT INTO album_artists(album_id, artist_id) ",
+        &snapshot.album_artists,
+        |mut row, link| {
+            row.push_bind(link.album_id).push_bind(link.artist_id);
+        },
+    )
+    .await?;
+    insert_rows(
+        &mut tx,
+        "INSERT INTO album_genres(album_id, genre_id) ",
+        &snapshot.album_genres,
+        |mut row, link| {
+            row.push_bind(link.album_id).push_bind(link.genre_id);
+        },
+    )
+    .await?;
+    insert_rows(
+        &mut tx,
+        "INSERT INTO album_moods(album_id, mood_id) ",
+        &snapshot.album_moods,
+        |mut row, link| {
+            row.push_bind(link.album_id).push_bind(link.mood_id);
+        },
+    )
+    .await?;
+    insert_rows(
+        &mut tx,
+        "INSERT INTO tracks(id, album_id, track_number, title, localized_title, artist) ",
+        &snapshot.tracks,
+        |mut row, track| {
+            row.push_bind(track.id)
+                .push_bind(track.album_id)
+                .push_bind(track.track_number)
+                .push_bind(&track.title)
+                .push_bind(&track.localized_title)
+                .push_bind(track.artist);
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    tracing::info!(
+        albums = snapshot.albums.len(),
+        artists = snapshot.artists.len(),
+        tracks = snapshot.tracks.len(),
+        "restored snapshot"
+    );
+    Ok(())
+}
+
+async fn insert_rows<'a, T>(
+    tx: &mut Transaction<'_, Postgres>,
+    insert: &str,
+    rows: &'a [T],
+    push_row: impl Fn(sqlx::query_builder::Separated<'_, 'a, Postgres, &'static str>, &'a T),
+) -> Result<()> {
+    for batch in rows.chunks(INSERT_BATCH) {
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(insert);
+        builder.push_values(batch, &push_row);
+        let rows = builder.build().execute(&mut **tx).await?.rows_affected();
+        tracing::debug!(rows, "{insert}");
+    }
+    Ok(())
+}
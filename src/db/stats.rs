@@ -0,0 +1,161 @@
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::filters::apply_album_filter;
+use crate::{
+    Result,
+    routes::AlbumFilter,
+    types::{PeriodStats, ScoreBucket, Stats, TagStats, VoterPercentiles},
+};
+
+const SCORE_BUCKET_WIDTH: f64 = 0.1;
+
+#[derive(Clone, Copy)]
+pub(super) enum TagKind {
+    Genre,
+    Mood,
+}
+
+impl TagKind {
+    /// Joins the tags of this kind onto `al`, as `tag` through `at`.
+    pub(super) fn join(&self) -> &'static str {
+        match self {
+            TagKind::Genre => {
+                "JOIN album_genres at ON at.album_id = al.id JOIN genres tag ON tag.id = at.genre_id"
+            }
+            TagKind::Mood => {
+                "JOIN album_moods at ON at.album_id = al.id JOIN moods tag ON tag.id = at.mood_id"
+            }
+        }
+    }
+
+    /// Joins every pair of distinct tags of this kind on `al`, as `source`
+    /// and `target` ids, each pair only once.
+    pub(super) fn pair_join(&self) -> &'static str {
+        match self {
+            TagKind::Genre => {
+                r#"JOIN (SELECT a.album_id, a.genre_id AS source, b.genre_id AS target
+                    FROM album_genres a
+                    JOIN album_genres b ON b.album_id = a.album_id AND a.genre_id < b.genre_id
+                ) pairs ON pairs.album_id = al.id"#
+            }
+            TagKind::Mood => {
+                r#"JOIN (SELECT a.album_id, a.mood_id AS source, b.mood_id AS target
+                    FROM album_moods a
+                    JOIN album_moods b ON b.album_id = a.album_id AND a.mood_id < b.mood_id
+                ) pairs ON pairs.album_id = al.id"#
+            }
+        }
+    }
+}
+
+/// Starts a query over every album matching `filters`, joined with `joins`
+/// and ready for a GROUP BY.
+pub(super) fn filtered_albums<'a>(
+    select: &str,
+    joins: &str,
+    filters: &'a AlbumFilter,
+) -> QueryBuilder<'a, Postgres> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(select);
+    builder.push(" FROM albums al ");
+    builder.push(joins);
+    builder.push(" WHERE 1=1");
+    apply_album_filter(&mut builder, filters);
+    builder
+}
+
+/// Album counts and scores for everything matching `filters`. Albums without
+/// any voters are counted, but left out of score averages and distributions.
+pub async fn get_stats(db: &PgPool, filters: &AlbumFilter) -> Result<Stats> {
+    let (albums, average_score): (i64, Option<f64>) = filtered_albums(
+        "SELECT COUNT(1), AVG(al.score) FILTER (WHERE al.voters > 0)::float8",
+        "",
+        filters,
+    )
+    .build_query_as()
+    .fetch_one(db)
+    .await?;
+
+    Ok(Stats {
+        albums,
+        average_score,
+        per_year: get_period_stats(db, "YYYY", None, filters).await?,
+        per_month: get_period_stats(db, "YYYY-MM", None, filters).await?,
+        per_genre: get_tag_stats(db, TagKind::Genre, filters).await?,
+        per_mood: get_tag_stats(db, TagKind::Mood, filters).await?,
+        score_distribution: get_score_distribution(db, filters).await?,
+        voter_percentiles: get_voter_percentiles(db, filters).await?,
+    })
+}
+
+/// Album counts and average scores per `to_char` formatted date, optionally
+/// only for albums tagged with the given genre or mood.
+pub(super) async fn get_period_stats(
+    db: &PgPool,
+    format: &str,
+    tag: Option<(TagKind, Uuid)>,
+    filters: &AlbumFilter,
+) -> Result<Vec<PeriodStats>> {
+    let mut builder = filtered_albums(
+        &format!(
+            r#"SELECT
+                to_char(al.date, '{format}') AS period,
+                COUNT(1) AS albums,
+                AVG(al.score) FILTER (WHERE al.voters > 0)::float8 AS average_score"#
+        ),
+        tag.map(|(kind, _)| kind.join()).unwrap_or_default(),
+        filters,
+    );
+    if let Some((_, tag_id)) = tag {
+        builder.push(" AND tag.id = ");
+        builder.push_bind(tag_id);
+    }
+    builder.push(" GROUP BY period ORDER BY period");
+    Ok(builder.build_query_as().fetch_all(db).await?)
+}
+
+async fn get_tag_stats(db: &PgPool, kind: TagKind, filters: &AlbumFilter) -> Result<Vec<TagStats>> {
+    let mut builder = filtered_albums(
+        r#"SELECT
+            tag.id,
+            tag.name,
+            COUNT(1) AS albums,
+            AVG(al.score) FILTER (WHERE al.voters > 0)::float8 AS average_score"#,
+        kind.join(),
+        filters,
+    );
+    builder.push(" GROUP BY tag.id ORDER BY albums DESC, tag.name");
+    Ok(builder.build_query_as().fetch_all(db).await?)
+}
+
+async fn get_score_distribution(db: &PgPool, filters: &AlbumFilter) -> Result<Vec<ScoreBucket>> {
+    // numeric keeps a REAL score like 3.9 from landing in the 3.8 bucket
+    let mut builder = filtered_albums(
+        &format!(
+            r#"SELECT
+                (FLOOR(al.score::numeric / {SCORE_BUCKET_WIDTH}) * {SCORE_BUCKET_WIDTH})::float8 AS score,
+                COUNT(1) AS albums"#
+        ),
+        "",
+        filters,
+    );
+    builder.push(" AND al.voters > 0 GROUP BY 1 ORDER BY 1");
+    Ok(builder.build_query_as().fetch_all(db).await?)
+}
+
+async fn get_voter_percentiles(db: &PgPool, filters: &AlbumFilter) -> Result<VoterPercentiles> {
+    Ok(filtered_albums(
+        r#"SELECT
+            percentile_cont(0.1) WITHIN GROUP (ORDER BY al.voters) AS p10,
+            percentile_cont(0.25) WITHIN GROUP (ORDER BY al.voters) AS p25,
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY al.voters) AS p50,
+            percentile_cont(0.75) WITHIN GROUP (ORDER BY al.voters) AS p75,
+            percentile_cont(0.9) WITHIN GROUP (ORDER BY al.voters) AS p90,
+            percentile_cont(0.99) WITHIN GROUP (ORDER BY al.voters) AS p99"#,
+        "",
+        filters,
+    )
+    .build_query_as()
+    .fetch_one(db)
+    .await?)
+}
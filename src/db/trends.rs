@@ -0,0 +1,115 @@
+use chrono::NaiveDate;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::{
+    filters::{
+        apply_artist_filter, apply_genre_filter, apply_limit, apply_mood_filter,
+        apply_rating_filter, apply_url_filter,
+    },
+    reads::{get_genre, get_mood},
+    stats::{TagKind, get_period_stats},
+};
+use crate::{
+    Result,
+    routes::AlbumFilter,
+    types::{GenreTrend, MoodTrend, TagGrowth},
+};
+
+pub async fn get_genre_trend(
+    db: &PgPool,
+    genre_id: Uuid,
+    filters: &AlbumFilter,
+) -> Result<GenreTrend> {
+    Ok(GenreTrend {
+        genre: get_genre(db, genre_id).await?,
+        months: get_period_stats(db, "YYYY-MM", Some((TagKind::Genre, genre_id)), filters).await?,
+    })
+}
+
+pub async fn get_mood_trend(
+    db: &PgPool,
+    mood_id: Uuid,
+    filters: &AlbumFilter,
+) -> Result<MoodTrend> {
+    Ok(MoodTrend {
+        mood: get_mood(db, mood_id).await?,
+        months: get_period_stats(db, "YYYY-MM", Some((TagKind::Mood, mood_id)), filters).await?,
+    })
+}
+
+pub async fn get_growing_genres(
+    db: &PgPool,
+    previous: (NaiveDate, NaiveDate),
+    current: (NaiveDate, NaiveDate),
+    page: i64,
+    limit: i64,
+    filters: &AlbumFilter,
+) -> Result<Vec<TagGrowth>> {
+    get_growing_tags(db, TagKind::Genre, previous, current, page, limit, filters).await
+}
+
+pub async fn get_growing_moods(
+    db: &PgPool,
+    previous: (NaiveDate, NaiveDate),
+    current: (NaiveDate, NaiveDate),
+    page: i64,
+    limit: i64,
+    filters: &AlbumFilter,
+) -> Result<Vec<TagGrowth>> {
+    get_growing_tags(db, TagKind::Mood, previous, current, page, limit, filters).await
+}
+
+/// Ranks tags by how many more albums they got in the `current` date range
+/// than in the `previous` one. The date range in `filters` is ignored, the
+/// two periods take its place.
+async fn get_growing_tags(
+    db: &PgPool,
+    kind: TagKind,
+    (previous_since, previous_to): (NaiveDate, NaiveDate),
+    (since, to): (NaiveDate, NaiveDate),
+    page: i64,
+    limit: i64,
+    filters: &AlbumFilter,
+) -> Result<Vec<TagGrowth>> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT * FROM (SELECT tag.id, tag.name, COUNT(1) FILTER (WHERE al.date BETWEEN ",
+    );
+    builder.push_bind(previous_since);
+    builder.push(" AND ");
+    builder.push_bind(previous_to);
+    builder.push(") AS previous_albums, COUNT(1) FILTER (WHERE al.date BETWEEN ");
+    builder.push_bind(since);
+    builder.push(" AND ");
+    builder.push_bind(to);
+    builder.push(") AS albums FROM albums al ");
+    builder.push(kind.join());
+    builder.push(" WHERE ((al.date BETWEEN ");
+    builder.push_bind(previous_since);
+    builder.push(" AND ");
+    builder.push_bind(previous_to);
+    builder.push(") OR (al.date BETWEEN ");
+    builder.push_bind(since);
+    builder.push(" AND ");
+    builder.push_bind(to);
+    builder.push("))");
+
+    apply_url_filter(&mut builder, filters.url.clone());
+    apply_genre_filter(&mut builder, &filters.genres);
+    apply_mood_filter(&mut builder, &filters.moods);
+    apply_artist_filter(&mut builder, &filters.artists);
+    apply_rating_filter(&mut builder, filters.min_rating);
+    builder.push(
+        " GROUP BY tag.id) AS growth ORDER BY albums - previous_albums DESC, albums DESC, name",
+    );
+    apply_limit(&mut builder, page, limit);
+
+    let mut tags: Vec<TagGrowth> = builder.build_query_as().fetch_all(db).await?;
+    for tag in &mut tags {
+        if tag.previous_albums > 0 {
+            tag.growth =
+                Some((tag.albums - tag.previous_albums) as f64 / tag.previous_albums as f64);
+        }
+    }
+    Ok(tags)
+}
@@ -1,22 +1,46 @@
-use sqlx::{PgPool, query_as};
+use sqlx::{PgPool, Postgres, Transaction, query, query_as};
 use uuid::Uuid;
 
 use crate::{
     Result,
-    error::AppError,
     types::{Album, Artist, Genre, InsertedAlbum, Mood, NewAlbum, NewArtist, NewTrack, Track},
 };
 
+#[tracing::instrument(skip_all, fields(url = %album.url))]
 pub async fn register_album(db: &PgPool, album: &NewAlbum) -> Result<Album> {
-    let inserted_album = add_album(album, db).await?;
-    let genres = add_genres(&album.genres, db).await?;
-    let artists = add_artists(&album.artists, db).await?;
-    let moods = add_moods(&album.moods, db).await?;
-    let tracks = add_tracks(inserted_album.id, &album.tracks, db).await?;
+    let registered = store_album(db, album).await;
+    if let Err(error) = &registered {
+        metrics::counter!("ingestion_failures_total").increment(1);
+        tracing::warn!(%error, "couldn't register album");
+    }
+    registered
+}
+
+/// Writes `album` and everything it links to in one transaction, so an album
+/// cut off halfway, by an error or by shutdown, leaves nothing behind.
+async fn store_album(db: &PgPool, album: &NewAlbum) -> Result<Album> {
+    let mut tx = db.begin().await?;
+    let (inserted_album, created) = add_album(album, &mut tx).await?;
+    let genres = add_genres(&album.genres, &mut tx).await?;
+    let artists = add_artists(album, &mut tx).await?;
+    let moods = add_moods(&album.moods, &mut tx).await?;
+    let tracks = add_tracks(inserted_album.id, &album.tracks, &mut tx).await?;
 
-    add_album_artists(&inserted_album, &artists, db).await?;
-    add_album_genres(&inserted_album, &genres, db).await?;
-    add_album_moods(&inserted_album, &moods, db).await?;
+    add_album_artists(&inserted_album, &artists, &mut tx).await?;
+    add_album_genres(&inserted_album, &genres, &mut tx).await?;
+    add_album_moods(&inserted_album, &moods, &mut tx).await?;
+    tx.commit().await?;
+    tracing::info!(
+        album_id = %inserted_album.id,
+        artists = artists.len(),
+        genres = genres.len(),
+        moods = moods.len(),
+        tracks = tracks.len(),
+        created,
+        "registered album"
+    );
+    let outcome = if created { "created" } else { "updated" };
+    metrics::counter!("albums_registered_total", "outcome" => outcome).increment(1);
     Ok(Album {
         id: inserted_album.id,
         title: inserted_album.title,
@@ -34,10 +58,15 @@ pub async fn register_album(db: &PgPool, album: &NewAlbum) -> Result<Album> {
     })
 }
 
-async fn add_album(album: &NewAlbum, db: &PgPool) -> Result<InsertedAlbum> {
-    let _: std::result::Result<InsertedAlbum, sqlx::Error> = query_as!(
-        InsertedAlbum,
-        "INSERT INTO albums(title, date, url, rym_url, score, voters, localized_title)
+/// Inserts or updates `album` by its url, and says whether it was new.
+async fn add_album(
+    album: &NewAlbum,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(InsertedAlbum, bool)> {
+    // xmax is only set on the row when the conflict turned the insert into an
+    // update.
+    let row = sqlx::query!(
+        r#"INSERT INTO albums(title, date, url, rym_url, score, voters, localized_title)
         VALUES ($1, $2, $3, $4, $5, $6, $7)
         ON CONFLICT (url) DO UPDATE
         SET title = $1,
@@ -48,7 +77,8 @@ async fn add_album(album: &NewAlbum, db: &PgPool) -> Result<InsertedAlbum> {
             voters = $6,
             localized_title = $7,
             modified_date = DEFAULT
-        RETURNING id, title, date, url, rym_url, score, voters, localized_title, modified_date",
+        RETURNING id, title, date, url, rym_url, score, voters, localized_title, modified_date,
+            (xmax = 0) AS "inserted!""#,
         album.album,
         album.date,
         album.url,
@@ -57,27 +87,28 @@ async fn add_album(album: &NewAlbum, db: &PgPool) -> Result<InsertedAlbum> {
         album.voters,
         album.localized_title
     )
-    .fetch_one(db)
-    .await;
-    let new_album = query_as!(
-        InsertedAlbum,
-        "SELECT * FROM albums WHERE url = $1",
-        album.url
-    )
-    .fetch_optional(db)
+    .fetch_one(&mut **tx)
     .await?;
-    if let Some(album) = new_album {
-        return Ok(album);
-    }
-    Err(AppError::Sqlx(sqlx::Error::ColumnNotFound(
-        "Couldn't find the row we just inserted".into(),
-    )))
+    Ok((
+        InsertedAlbum {
+            id: row.id,
+            title: row.title,
+            localized_title: row.localized_title,
+            date: row.date,
+            url: row.url,
+            rym_url: row.rym_url,
+            score: row.score,
+            voters: row.voters,
+            modified_date: row.modified_date,
+        },
+        row.inserted,
+    ))
 }
 
 async fn add_album_artists(
     album: &InsertedAlbum,
     artists: &Vec<Artist>,
-    db: &PgPool,
+    tx: &mut Transaction<'_, Postgres>,
 ) -> Result<()> {
     for artist in artists {
         sqlx::query!(
@@ -85,66 +116,136 @@ async fn add_album_artists(
             album.id,
             artist.id
         )
-        .execute(db)
+        .execute(&mut **tx)
         .await?;
     }
     Ok(())
 }
 
-async fn add_album_genres(album: &InsertedAlbum, genres: &Vec<Genre>, db: &PgPool) -> Result<()> {
+async fn add_album_genres(
+    album: &InsertedAlbum,
+    genres: &Vec<Genre>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
     for genre in genres {
         sqlx::query!(
             "INSERT INTO album_genres(album_id, genre_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
             album.id,
             genre.id
         )
-        .execute(db)
+        .execute(&mut **tx)
         .await?;
     }
     Ok(())
 }
 
-async fn add_album_moods(album: &InsertedAlbum, moods: &Vec<Mood>, db: &PgPool) -> Result<()> {
+async fn add_album_moods(
+    album: &InsertedAlbum,
+    moods: &Vec<Mood>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
     for mood in moods {
         sqlx::query!(
             "INSERT INTO album_moods(album_id, mood_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
             album.id,
             mood.id
         )
-        .execute(db)
+        .execute(&mut **tx)
         .await?;
     }
     Ok(())
 }
 
-async fn add_genres(genres: &[String], db: &PgPool) -> Result<Vec<Genre>> {
-    let stream = genres
-        .iter()
-        .map(async |g| get_or_create_genre(g.to_string(), db).await.unwrap());
-    Ok(futures::future::join_all(stream).await)
+// Concurrent registrations lock the genres, moods and artists they upsert
+// until they commit. Upserting them in name order means two albums sharing
+// some never wait on each other in opposite orders, which would deadlock.
+
+async fn add_genres(genres: &[String], tx: &mut Transaction<'_, Postgres>) -> Result<Vec<Genre>> {
+    for name in sorted(genres.iter()) {
+        query!(
+            "INSERT INTO genres(name) VALUES ($1) ON CONFLICT DO NOTHING",
+            name
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    let mut added = Vec::with_capacity(genres.len());
+    for name in genres {
+        let genre = query_as!(Genre, "SELECT * FROM genres WHERE name = $1", name)
+            .fetch_one(&mut **tx)
+            .await?;
+        added.push(genre);
+    }
+    Ok(added)
 }
 
-async fn add_moods(moods: &[String], db: &PgPool) -> Result<Vec<Mood>> {
-    let stream = moods
-        .iter()
-        .map(async |m| get_or_create_mood(m.to_string(), db).await.unwrap());
-    Ok(futures::future::join_all(stream).await)
+async fn add_moods(moods: &[String], tx: &mut Transaction<'_, Postgres>) -> Result<Vec<Mood>> {
+    for name in sorted(moods.iter()) {
+        query!(
+            "INSERT INTO moods(name) VALUES ($1) ON CONFLICT DO NOTHING",
+            name
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    let mut added = Vec::with_capacity(moods.len());
+    for name in moods {
+        let mood = query_as!(Mood, "SELECT * FROM moods WHERE name = $1", name)
+            .fetch_one(&mut **tx)
+            .await?;
+        added.push(mood);
+    }
+    Ok(added)
 }
 
-async fn add_artists(artists: &[NewArtist], db: &PgPool) -> Result<Vec<Artist>> {
-    let stream = artists
+/// Upserts the album's artists along with the ones featured on its tracks,
+/// and returns the album's.
+async fn add_artists(album: &NewAlbum, tx: &mut Transaction<'_, Postgres>) -> Result<Vec<Artist>> {
+    let featured = album
+        .tracks
         .iter()
-        .map(async |a| get_or_create_artist(a.clone(), db).await.unwrap());
-    Ok(futures::future::join_all(stream).await)
+        .filter_map(|track| track.artist.as_ref());
+    let mut credited: Vec<&NewArtist> = album.artists.iter().chain(featured).collect();
+    // Stable, so a track's localized name still wins over the album's.
+    credited.sort_by(|a, b| a.name.cmp(&b.name));
+    for artist in credited {
+        query!(
+            "INSERT INTO artists(name, localized_name)
+            VALUES ($1, $2)
+            ON CONFLICT (name)
+            DO UPDATE SET localized_name = $2",
+            artist.name,
+            artist.localized_name
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    let mut added = Vec::with_capacity(album.artists.len());
+    for artist in &album.artists {
+        added.push(get_artist(&artist.name, tx).await?);
+    }
+    Ok(added)
 }
 
-async fn add_tracks(album_id: Uuid, tracks: &[NewTrack], db: &PgPool) -> Result<Vec<Track>> {
-    sqlx::query!("DELETE FROM tracks WHERE album_id = $1", album_id)
-        .execute(db)
-        .await?;
+fn sorted<T: Ord>(items: impl Iterator<Item = T>) -> Vec<T> {
+    let mut items: Vec<T> = items.collect();
+    items.sort();
+    items
+}
+
+async fn add_tracks(
+    album_id: Uuid,
+    tracks: &[NewTrack],
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<Track>> {
+    let deleted = sqlx::query!("DELETE FROM tracks WHERE album_id = $1", album_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+    tracing::debug!(deleted, inserted = tracks.len(), "replacing tracks");
     for track in tracks {
         let artist_id = if let Some(artist) = &track.artist {
-            Some(get_or_create_artist(artist.clone(), db).await?.id)
+            Some(get_artist(&artist.name, tx).await?.id)
         } else {
             None
         };
@@ -157,7 +258,7 @@ async fn add_tracks(album_id: Uuid, tracks: &[NewTrack], db: &PgPool) -> Result<
             artist_id,
             track.localized_title
         )
-        .execute(db)
+        .execute(&mut **tx)
         .await?;
     }
     let returned_tracks = sqlx::query_as!(
@@ -169,54 +270,15 @@ async fn add_tracks(album_id: Uuid, tracks: &[NewTrack], db: &PgPool) -> Result<
         ORDER BY t.track_number"#,
         album_id
     )
-    .fetch_all(db)
+    .fetch_all(&mut **tx)
     .await?;
     Ok(returned_tracks)
 }
 
-async fn get_or_create_genre(genre: String, db: &PgPool) -> Result<Genre> {
-    let _ = query_as!(
-        Genre,
-        "INSERT INTO genres(name) VALUES ($1) ON CONFLICT DO NOTHING RETURNING id, name",
-        genre
-    )
-    .fetch_one(db)
-    .await;
-    let genre = query_as!(Genre, "SELECT * FROM genres WHERE name = $1", genre)
-        .fetch_one(db)
-        .await?;
-    Ok(genre)
-}
-
-async fn get_or_create_mood(mood: String, db: &PgPool) -> Result<Mood> {
-    let _ = query_as!(
-        Mood,
-        "INSERT INTO moods(name) VALUES ($1) ON CONFLICT DO NOTHING RETURNING id, name",
-        mood
-    )
-    .fetch_one(db)
-    .await;
-    let mood = query_as!(Mood, "SELECT * FROM moods WHERE name = $1", mood)
-        .fetch_one(db)
-        .await?;
-    Ok(mood)
-}
-
-async fn get_or_create_artist(artist: NewArtist, db: &PgPool) -> Result<Artist> {
-    let _ = query_as!(
-        Artist,
-        r#"INSERT INTO artists(name, localized_name) 
-        VALUES ($1, $2)
-        ON CONFLICT (name) 
-        DO UPDATE SET localized_name = $2
-        RETURNING id, name, localized_name"#,
-        artist.name,
-        artist.localized_name
-    )
-    .fetch_one(db)
-    .await;
-    let artist = query_as!(Artist, "SELECT * FROM artists WHERE name = $1", artist.name)
-        .fetch_one(db)
+/// An artist `add_artists` has upserted.
+async fn get_artist(name: &str, tx: &mut Transaction<'_, Postgres>) -> Result<Artist> {
+    let artist = query_as!(Artist, "SELECT * FROM artists WHERE name = $1", name)
+        .fetch_one(&mut **tx)
         .await?;
     Ok(artist)
 }
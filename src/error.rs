@@ -14,6 +14,9 @@ pub enum AppError {
     VarError(env::VarError),
     IoError(std::io::Error),
     UuidError(uuid::Error),
+    Template(askama::Error),
+    Migrate(sqlx::migrate::MigrateError),
+    BadRequest(String),
 }
 
 impl Display for AppError {
@@ -26,12 +29,22 @@ impl Display for AppError {
             AppError::IoError(error) => error.fmt(f),
             AppError::UuidError(error) => error.fmt(f),
             AppError::Deserialize(error) => error.fmt(f),
+            AppError::Template(error) => error.fmt(f),
+            AppError::Migrate(error) => error.fmt(f),
+            AppError::BadRequest(message) => message.fmt(f),
         }
     }
 }
 
+impl std::error::Error for AppError {}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        if let AppError::BadRequest(message) = self {
+            tracing::info!(%message, "bad request");
+            return (StatusCode::BAD_REQUEST, message).into_response();
+        }
+        tracing::error!(error = %self, "request failed");
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Can't do what we wanted...: {self}"),
@@ -81,3 +94,15 @@ impl From<serde_json::Error> for AppError {
         Self::Deserialize(err)
     }
 }
+
+impl From<askama::Error> for AppError {
+    fn from(err: askama::Error) -> Self {
+        Self::Template(err)
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for AppError {
+    fn from(err: sqlx::migrate::MigrateError) -> Self {
+        Self::Migrate(err)
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{Result, types::Album};
+
+pub const CSV_HEADER: &str = "id,title,localized_title,artists,date,genres,moods,tracks,url,rym_url,score,voters,modified_date\r\n";
+
+/// One CSV record per album, with artists, genres, moods and tracks each
+/// flattened into a single `; ` separated field.
+pub fn to_csv_row(album: &Album) -> String {
+    let artists = album
+        .artists
+        .iter()
+        .flatten()
+        .map(|artist| artist.name.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let genres = album
+        .genres
+        .iter()
+        .flatten()
+        .map(|genre| genre.name.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let moods = album
+        .moods
+        .iter()
+        .flatten()
+        .map(|mood| mood.name.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+    let tracks = album
+        .tracks
+        .iter()
+        .flatten()
+        .map(|track| match &track.artist {
+            Some(artist) => format!("{}. {} ({})", track.track_number, track.title, artist.name),
+            None => format!("{}. {}", track.track_number, track.title),
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let fields = [
+        album.id.to_string(),
+        album.title.clone(),
+        album.localized_title.clone().unwrap_or_default(),
+        artists,
+        album.date.format("%Y-%m-%d").to_string(),
+        genres,
+        moods,
+        tracks,
+        album.url.clone(),
+        album.rym_url.clone().unwrap_or_default(),
+        album.score.to_string(),
+        album.voters.to_string(),
+        album.modified_date.format("%Y-%m-%d").to_string(),
+    ];
+    let mut row = fields
+        .iter()
+        .map(|field| escape_csv(field))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+pub fn to_ndjson_row(album: &Album) -> Result<String> {
+    let mut row = serde_json::to_string(album)?;
+    row.push('\n');
+    Ok(row)
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
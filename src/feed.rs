@@ -0,0 +1,144 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+
+use crate::{types::Album, xml::escape_xml};
+
+/// Renders `albums` as an Atom feed, `self_url` being where it's served from.
+pub fn to_atom(albums: &[Album], title: &str, self_url: &str) -> String {
+    let updated = albums
+        .iter()
+        .map(modified_at)
+        .max()
+        .unwrap_or_else(Utc::now);
+    let mut atom = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    atom.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(atom, "  <id>{}</id>", escape_xml(self_url));
+    let _ = writeln!(atom, "  <title>{}</title>", escape_xml(title));
+    let _ = writeln!(atom, "  <updated>{}</updated>", updated.to_rfc3339());
+    let _ = writeln!(
+        atom,
+        "  <link rel=\"self\" href=\"{}\"/>",
+        escape_xml(self_url)
+    );
+    let _ = writeln!(atom, "  <author><name>music-rs</name></author>");
+    for album in albums {
+        atom.push_str("  <entry>\n");
+        let _ = writeln!(atom, "    <id>urn:uuid:{}</id>", album.id);
+        let _ = writeln!(
+            atom,
+            "    <title>{}</title>",
+            escape_xml(&album.credited_title())
+        );
+        let _ = writeln!(
+            atom,
+            "    <published>{}</published>",
+            created_at(album).to_rfc3339()
+        );
+        let _ = writeln!(
+            atom,
+            "    <updated>{}</updated>",
+            modified_at(album).to_rfc3339()
+        );
+        let _ = writeln!(atom, "    <link href=\"{}\"/>", escape_xml(&album.url));
+        if let Some(rym_url) = &album.rym_url {
+            let _ = writeln!(
+                atom,
+                "    <link rel=\"related\" href=\"{}\"/>",
+                escape_xml(rym_url)
+            );
+        }
+        let _ = writeln!(
+            atom,
+            "    <summary>{}</summary>",
+            escape_xml(&describe(album))
+        );
+        atom.push_str("  </entry>\n");
+    }
+    atom.push_str("</feed>\n");
+    atom
+}
+
+/// Renders `albums` as an RSS 2.0 feed, `self_url` being where it's served from.
+pub fn to_rss(albums: &[Album], title: &str, self_url: &str) -> String {
+    let mut rss = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    rss.push_str("<rss version=\"2.0\">\n  <channel>\n");
+    let _ = writeln!(rss, "    <title>{}</title>", escape_xml(title));
+    let _ = writeln!(rss, "    <link>{}</link>", escape_xml(self_url));
+    let _ = writeln!(rss, "    <description>{}</description>", escape_xml(title));
+    for album in albums {
+        rss.push_str("    <item>\n");
+        let _ = writeln!(
+            rss,
+            "      <title>{}</title>",
+            escape_xml(&album.credited_title())
+        );
+        let _ = writeln!(rss, "      <link>{}</link>", escape_xml(&album.url));
+        let _ = writeln!(
+            rss,
+            "      <guid isPermaLink=\"false\">urn:uuid:{}</guid>",
+            album.id
+        );
+        let _ = writeln!(
+            rss,
+            "      <pubDate>{}</pubDate>",
+            created_at(album).to_rfc2822()
+        );
+        let _ = writeln!(
+            rss,
+            "      <description>{}</description>",
+            escape_xml(&describe(album))
+        );
+        rss.push_str("    </item>\n");
+    }
+    rss.push_str("  </channel>\n</rss>\n");
+    rss
+}
+
+fn describe(album: &Album) -> String {
+    let mut description = format!(
+        "Released {}, rated {:.2} by {} voters.",
+        album.date.format("%Y-%m-%d"),
+        album.score,
+        album.voters
+    );
+    let genres = album
+        .genres
+        .iter()
+        .flatten()
+        .map(|genre| genre.name.as_str())
+        .collect::<Vec<_>>();
+    if !genres.is_empty() {
+        let _ = write!(description, "\nGenres: {}", genres.join(", "));
+    }
+    let moods = album
+        .moods
+        .iter()
+        .flatten()
+        .map(|mood| mood.name.as_str())
+        .collect::<Vec<_>>();
+    if !moods.is_empty() {
+        let _ = write!(description, "\nMoods: {}", moods.join(", "));
+    }
+    let _ = write!(description, "\n{}", album.url);
+    if let Some(rym_url) = &album.rym_url {
+        let _ = write!(description, "\n{rym_url}");
+    }
+    description
+}
+
+fn modified_at(album: &Album) -> DateTime<Utc> {
+    album.modified_date.and_time(Default::default()).and_utc()
+}
+
+/// When the album was first added, going by its uuidv7 id.
+fn created_at(album: &Album) -> DateTime<Utc> {
+    album
+        .id
+        .get_timestamp()
+        .and_then(|timestamp| {
+            let (seconds, nanos) = timestamp.to_unix();
+            DateTime::from_timestamp(seconds as i64, nanos)
+        })
+        .unwrap_or_else(|| modified_at(album))
+}
@@ -0,0 +1,114 @@
+use std::fmt::Write;
+
+use crate::{types::Graph, xml::escape_xml};
+
+/// Renders `graph` as an undirected GraphViz graph called `name`, with the
+/// area of each node growing with its size.
+pub fn to_dot(graph: &Graph, name: &str) -> String {
+    let mut dot = format!("graph \"{}\" {{\n", escape_dot(name));
+    for node in &graph.nodes {
+        let diameter = node_diameter(node.size);
+        let _ = writeln!(
+            dot,
+            "  \"{}\" [label=\"{}\", width={diameter:.2}, height={diameter:.2}, fixedsize=true];",
+            node.id,
+            escape_dot(&node.name),
+        );
+    }
+    for link in &graph.links {
+        let _ = writeln!(
+            dot,
+            "  \"{}\" -- \"{}\" [weight={}];",
+            link.source, link.target, link.weight
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Renders `graph` as an undirected GraphML graph with `name` as its id.
+pub fn to_graphml(graph: &Graph, name: &str) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="name" for="node" attr.name="name" attr.type="string"/>
+  <key id="size" for="node" attr.name="size" attr.type="long"/>
+  <key id="weight" for="edge" attr.name="weight" attr.type="long"/>
+"#,
+    );
+    let _ = writeln!(
+        xml,
+        "  <graph id=\"{}\" edgedefault=\"undirected\">",
+        escape_xml(name)
+    );
+    for node in &graph.nodes {
+        let _ = writeln!(
+            xml,
+            "    <node id=\"{}\"><data key=\"name\">{}</data><data key=\"size\">{}</data></node>",
+            node.id,
+            escape_xml(&node.name),
+            node.size
+        );
+    }
+    for link in &graph.links {
+        let _ = writeln!(
+            xml,
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"weight\">{}</data></edge>",
+            link.source, link.target, link.weight
+        );
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
+
+/// In inches, so a node with a single album is about GraphViz's default height.
+fn node_diameter(size: i64) -> f64 {
+    0.5 * (size.max(1) as f64).sqrt()
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::types::{GraphLink, GraphNode};
+
+    #[test]
+    fn dot() {
+        let (a, b) = (Uuid::nil(), Uuid::max());
+        let graph = Graph {
+            nodes: vec![
+                GraphNode {
+                    id: a,
+                    name: "shoegaze".to_string(),
+                    size: 1,
+                },
+                GraphNode {
+                    id: b,
+                    name: "\"dream\" pop".to_string(),
+                    size: 16,
+                },
+            ],
+            links: vec![GraphLink {
+                source: a,
+                target: b,
+                weight: 3,
+            }],
+        };
+        assert_eq!(
+            to_dot(&graph, "genres"),
+            format!(
+                r#"graph "genres" {{
+  "{a}" [label="shoegaze", width=0.50, height=0.50, fixedsize=true];
+  "{b}" [label="\"dream\" pop", width=2.00, height=2.00, fixedsize=true];
+  "{a}" -- "{b}" [weight=3];
+}}
+"#
+            )
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    response::Html,
+};
+use uuid::Uuid;
+
+use crate::{
+    ApiContext, Result, db,
+    routes::{AlbumFilter, get_pagination_params},
+    types::{Album, Artist, SimilarAlbum, SimilarArtist, SimilarityMetric},
+};
+
+const RELATED_TAGS: usize = 20;
+const SIMILAR_ALBUMS: i64 = 10;
+const SIMILAR_ARTISTS: i64 = 10;
+
+/// Whether `url` is safe to link to. Album urls come from whoever posts to
+/// `/update`, and a `javascript:` one would run on the page.
+pub fn is_web_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// The current filters as they go back into the filter form.
+struct FilterForm {
+    genres: String,
+    moods: String,
+    artists: String,
+    min_rating: String,
+    since: String,
+    to: String,
+    limit: String,
+    page: i64,
+    has_next: bool,
+}
+
+impl FilterForm {
+    fn new(album_filter: &AlbumFilter, page: i64, has_next: bool) -> Self {
+        let show = |value: Option<String>| value.unwrap_or_default();
+        FilterForm {
+            genres: album_filter.genres.join(", "),
+            moods: album_filter.moods.join(", "),
+            artists: album_filter.artists.join(", "),
+            min_rating: show(album_filter.min_rating.map(|r| r.to_string())),
+            since: show(album_filter.since.map(|d| d.to_string())),
+            to: show(album_filter.to.map(|d| d.to_string())),
+            limit: show((album_filter.limit != 0).then(|| album_filter.limit.to_string())),
+            page,
+            has_next,
+        }
+    }
+}
+
+struct RelatedTag {
+    id: Uuid,
+    name: String,
+}
+
+#[derive(Template)]
+#[template(path = "albums.html")]
+struct AlbumsPage {
+    albums: Vec<Album>,
+    form: FilterForm,
+}
+
+#[derive(Template)]
+#[template(path = "album.html")]
+struct AlbumPage {
+    album: Album,
+    similar_albums: Vec<SimilarAlbum>,
+}
+
+#[derive(Template)]
+#[template(path = "tag.html")]
+struct TagPage {
+    kind: &'static str,
+    name: String,
+    related: Vec<RelatedTag>,
+    albums: Vec<Album>,
+    form: FilterForm,
+}
+
+#[derive(Template)]
+#[template(path = "artist.html")]
+struct ArtistPage {
+    artist: Artist,
+    similar_artists: Vec<SimilarArtist>,
+    albums: Vec<Album>,
+    form: FilterForm,
+}
+
+pub async fn albums(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Html<String>> {
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    let albums = db::get_albums(&state.db, page, limit, &album_filter).await?;
+    let form = FilterForm::new(&album_filter, page, albums.len() as i64 == limit);
+    Ok(Html(AlbumsPage { albums, form }.render()?))
+}
+
+pub async fn album(
+    State(state): State<ApiContext>,
+    Path(album_id): Path<String>,
+) -> Result<Html<String>> {
+    let id = Uuid::parse_str(&album_id)?;
+    let album = db::get_album(&state.db, id).await?;
+    let similar_albums = db::get_similar_albums(
+        &state.db,
+        id,
+        1,
+        SIMILAR_ALBUMS,
+        &AlbumFilter::default(),
+        &Default::default(),
+    )
+    .await?;
+    Ok(Html(
+        AlbumPage {
+            album,
+            similar_albums,
+        }
+        .render()?,
+    ))
+}
+
+pub async fn genre(
+    State(state): State<ApiContext>,
+    Path(genre): Path<String>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Html<String>> {
+    let genre_id = Uuid::parse_str(&genre)?;
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    let genre = db::get_genre(&state.db, genre_id).await?;
+    let related = db::get_similar_genres(&state.db, genre_id, SimilarityMetric::default(), 1)
+        .await?
+        .into_iter()
+        .take(RELATED_TAGS)
+        .map(|similar| RelatedTag {
+            id: similar.id,
+            name: similar.name.unwrap_or_default(),
+        })
+        .collect();
+
+    let mut genre_filter = album_filter.0.clone();
+    genre_filter.genres.push(genre.name.clone());
+    let albums = db::get_albums(&state.db, page, limit, &genre_filter).await?;
+    let form = FilterForm::new(&album_filter, page, albums.len() as i64 == limit);
+    Ok(Html(
+        TagPage {
+            kind: "genre",
+            name: genre.name,
+            related,
+            albums,
+            form,
+        }
+        .render()?,
+    ))
+}
+
+pub async fn mood(
+    State(state): State<ApiContext>,
+    Path(mood): Path<String>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Html<String>> {
+    let mood_id = Uuid::parse_str(&mood)?;
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    let mood = db::get_mood(&state.db, mood_id).await?;
+    let related = db::get_similar_moods(&state.db, mood_id, SimilarityMetric::default(), 1)
+        .await?
+        .into_iter()
+        .take(RELATED_TAGS)
+        .map(|similar| RelatedTag {
+            id: similar.id,
+            name: similar.name.unwrap_or_default(),
+        })
+        .collect();
+
+    let mut mood_filter = album_filter.0.clone();
+    mood_filter.moods.push(mood.name.clone());
+    let albums = db::get_albums(&state.db, page, limit, &mood_filter).await?;
+    let form = FilterForm::new(&album_filter, page, albums.len() as i64 == limit);
+    Ok(Html(
+        TagPage {
+            kind: "mood",
+            name: mood.name,
+            related,
+            albums,
+            form,
+        }
+        .render()?,
+    ))
+}
+
+pub async fn artist(
+    State(state): State<ApiContext>,
+    Path(artist_id): Path<String>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Html<String>> {
+    let id = Uuid::parse_str(&artist_id)?;
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    let artist = db::get_artist(&state.db, id).await?;
+    let similar_artists = db::get_similar_artists(&state.db, id, 1, SIMILAR_ARTISTS).await?;
+
+    let mut artist_filter = album_filter.0.clone();
+    artist_filter.artists.push(artist.name.clone());
+    let albums = db::get_albums(&state.db, page, limit, &artist_filter).await?;
+    let form = FilterForm::new(&album_filter, page, albums.len() as i64 == limit);
+    Ok(Html(
+        ArtistPage {
+            artist,
+            similar_artists,
+            albums,
+            form,
+        }
+        .render()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn web_urls() {
+        assert!(is_web_url("https://x.bandcamp.com/album/a"));
+        assert!(is_web_url("HTTP://rateyourmusic.com/release/album/a/b/"));
+        assert!(!is_web_url("javascript:alert(1)"));
+        assert!(!is_web_url(" javascript:alert(1)"));
+        assert!(!is_web_url("data:text/html,<script>alert(1)</script>"));
+        assert!(!is_web_url("//evil.example"));
+        assert!(!is_web_url(""));
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt::Write;
+
+use chrono::Days;
+
+use crate::types::Album;
+
+/// Renders one all-day event per album on its release date.
+pub fn to_ical(albums: &[Album]) -> String {
+    let mut ical = String::new();
+    push_line(&mut ical, "BEGIN:VCALENDAR");
+    push_line(&mut ical, "VERSION:2.0");
+    push_line(&mut ical, "PRODID:-//music-rs//releases//EN");
+    push_line(&mut ical, "CALSCALE:GREGORIAN");
+    push_line(&mut ical, "X-WR-CALNAME:music-rs releases");
+    for album in albums {
+        let mut description = album.url.clone();
+        if let Some(rym_url) = &album.rym_url {
+            let _ = write!(description, "\n{rym_url}");
+        }
+
+        push_line(&mut ical, "BEGIN:VEVENT");
+        push_line(&mut ical, &format!("UID:{}@music-rs", album.id));
+        push_line(
+            &mut ical,
+            &format!("DTSTAMP:{}T000000Z", album.modified_date.format("%Y%m%d")),
+        );
+        push_line(
+            &mut ical,
+            &format!("DTSTART;VALUE=DATE:{}", album.date.format("%Y%m%d")),
+        );
+        push_line(
+            &mut ical,
+            &format!(
+                "DTEND;VALUE=DATE:{}",
+                (album.date + Days::new(1)).format("%Y%m%d")
+            ),
+        );
+        push_line(
+            &mut ical,
+            &format!("SUMMARY:{}", escape(&album.credited_title())),
+        );
+        push_line(&mut ical, &format!("DESCRIPTION:{}", escape(&description)));
+        push_line(&mut ical, &format!("URL:{}", album.url));
+        push_line(&mut ical, "END:VEVENT");
+    }
+    push_line(&mut ical, "END:VCALENDAR");
+    ical
+}
+
+/// Ends `line` with CRLF, folding it so no line is longer than 75 octets.
+fn push_line(ical: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ical.push_str("\r\n ");
+            width = 1;
+        }
+        ical.push(c);
+        width += c.len_utf8();
+    }
+    ical.push_str("\r\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
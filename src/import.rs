@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::{Result, db, types::NewAlbum};
+
+pub const DEFAULT_BATCH_SIZE: usize = 50;
+
+/// Something found at a location in the imported file, like `line 3, album 2`.
+type Located<T> = (String, T);
+
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Where in the file each failed album was, and why it failed.
+    pub failures: Vec<Located<String>>,
+}
+
+/// Registers every album in `path`, `batch_size` at a time.
+///
+/// The file is either a single JSON value or JSON lines, where each value is
+/// one album or an array of them, like the bodies POSTed to `/update`.
+pub async fn import_file(db: &PgPool, path: &Path, batch_size: usize) -> Result<ImportSummary> {
+    let contents = tokio::fs::read_to_string(path).await?;
+    let (albums, failures) = parse_albums(&contents);
+    let mut summary = ImportSummary {
+        imported: 0,
+        failures,
+    };
+
+    for batch in albums.chunks(batch_size.max(1)) {
+        let results =
+            futures::future::join_all(batch.iter().map(|(_, album)| db::register_album(db, album)))
+                .await;
+        for ((location, album), result) in batch.iter().zip(results) {
+            match result {
+                Ok(_) => summary.imported += 1,
+                Err(e) => summary
+                    .failures
+                    .push((format!("{location} ({})", album.url), e.to_string())),
+            }
+        }
+        println!("imported {} albums so far", summary.imported);
+    }
+    Ok(summary)
+}
+
+/// The albums in `contents`, and where and why each entry that isn't one
+/// failed to parse.
+fn parse_albums(contents: &str) -> (Vec<Located<NewAlbum>>, Vec<Located<String>>) {
+    let values: Vec<Located<serde_json::Result<Value>>> = match serde_json::from_str(contents) {
+        Ok(value) => vec![("document".to_string(), Ok(value))],
+        Err(_) => contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (format!("line {}", i + 1), serde_json::from_str(line)))
+            .collect(),
+    };
+
+    let mut albums = Vec::new();
+    let mut failures = Vec::new();
+    for (location, value) in values {
+        let entries = match value {
+            Ok(Value::Array(entries)) => entries
+                .into_iter()
+                .enumerate()
+                .map(|(i, entry)| (format!("{location}, album {}", i + 1), entry))
+                .collect(),
+            Ok(entry) => vec![(location, entry)],
+            Err(e) => {
+                failures.push((location, format!("not valid json: {e}")));
+                continue;
+            }
+        };
+        for (location, entry) in entries {
+            match serde_json::from_value(entry) {
+                Ok(album) => albums.push((location, album)),
+                Err(e) => failures.push((location, e.to_string())),
+            }
+        }
+    }
+    (albums, failures)
+}
+
+/// Imports `path`, printing a summary of what failed. Fails if any album did.
+pub async fn run(db: &PgPool, path: &Path, batch_size: usize) -> Result<()> {
+    let summary = import_file(db, path, batch_size).await?;
+    println!(
+        "imported {} albums, {} failed",
+        summary.imported,
+        summary.failures.len()
+    );
+    for (location, error) in &summary.failures {
+        println!("  {location}: {error}");
+    }
+    if !summary.failures.is_empty() {
+        return Err(anyhow::anyhow!("{} albums failed to import", summary.failures.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALBUM: &str = r#"{"album": "A", "localized_title": null, "artists": [], "date": "2024-11-13", "genres": [], "moods": [], "tracks": [], "url": "https://a.bandcamp.com/album/a", "rym_url": "", "score": 3.5, "voters": 10}"#;
+
+    fn locations<T>(entries: &[(String, T)]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|(location, _)| location.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn parses_a_single_document() {
+        let (albums, failures) = parse_albums(&format!("[{ALBUM}, {ALBUM}]"));
+        assert_eq!(
+            locations(&albums),
+            ["document, album 1", "document, album 2"]
+        );
+        assert!(failures.is_empty());
+
+        let (albums, failures) = parse_albums(ALBUM);
+        assert_eq!(locations(&albums), ["document"]);
+        assert!(failures.is_empty());
+    }
+
+    #[test]
+    fn parses_json_lines() {
+        let contents = format!("{ALBUM}\n\n[{ALBUM}, {{}}]\nnot json\n");
+        let (albums, failures) = parse_albums(&contents);
+        assert_eq!(locations(&albums), ["line 1", "line 3, album 1"]);
+        assert_eq!(locations(&failures), ["line 3, album 2", "line 4"]);
+        assert!(failures[1].1.starts_with("not valid json: "));
+    }
+}
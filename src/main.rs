@@ -1,53 +1,304 @@
-use std::{env, time::Duration};
+use std::{
+    env, fmt::Debug, future::IntoFuture, hash::Hash, net::SocketAddr, path::PathBuf, sync::Arc,
+    time::Duration,
+};
 
 use axum::{
-    Router,
-    routing::{get, post},
+    Router, middleware,
+    routing::{MethodRouter, get, post},
 };
-use axum_response_cache::CacheLayer;
+use axum_response_cache::{BasicKeyer, CacheLayer, Keyer};
+use clap::Parser;
+use cli::{Cli, Command, ServeArgs};
+use config::Config;
 use dotenv::dotenv;
+use metrics_exporter_prometheus::PrometheusHandle;
 use sqlx::{PgPool, postgres::PgPoolOptions};
+use tokio::sync::oneshot;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
 
+mod auth;
+mod cli;
+mod config;
 mod db;
 mod error;
+mod export;
+mod feed;
+mod graph;
+mod html;
+mod ical;
+mod import;
 mod routes;
+mod snapshot;
+mod telemetry;
 mod types;
+mod xml;
 
 type Result<T> = std::result::Result<T, error::AppError>;
 
 #[derive(Clone)]
 struct ApiContext {
     pub db: PgPool,
+    pub config: Arc<Config>,
+    pub metrics: PrometheusHandle,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
     dotenv().ok();
 
+    let config_file = cli
+        .config
+        .or_else(|| env::var_os("MUSIC_CONFIG").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(config::DEFAULT_CONFIG_FILE));
+    let config = Config::load(&config_file)?;
+    telemetry::init(&config.log)?;
+
     let db = PgPoolOptions::new()
-        .max_connections(50)
-        .connect(&env::var("DATABASE_URL")?)
-        .await?;
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .acquire_timeout(Duration::from_secs(config.database.acquire_timeout_secs))
+        .idle_timeout(config.database.idle_timeout_secs.map(Duration::from_secs))
+        .connect_lazy(&config.database.url()?)?;
+
+    match cli.command.unwrap_or_default() {
+        Command::Serve(args) => serve(db, config, args).await,
+        Command::Migrate { command } => cli::migrate(&db, command.unwrap_or_default()).await,
+        Command::Import { file, batch_size } => import::run(&db, &file, batch_size).await,
+        Command::Export(args) => cli::export(db, args).await,
+        Command::ExportSnapshot { file } => snapshot::run_export(&db, &file).await,
+        Command::ImportSnapshot { file } => snapshot::run_import(&db, &file).await,
+        Command::Stats(filters) => cli::stats(&db, filters).await,
+        Command::MergeArtists { from, into } => cli::merge_artists(&db, from, into).await,
+        Command::DeleteAlbum { id } => cli::delete_album(&db, id).await,
+    }
+}
+
+async fn serve(db: PgPool, mut config: Config, args: ServeArgs) -> Result<()> {
+    config.server.bind = args.bind.unwrap_or(config.server.bind);
+    config.server.port = args.port.unwrap_or(config.server.port);
+    // Serve while the database is down or behind, /readyz tells the
+    // supervisor when it's caught up. Migrations that fail on their own shut
+    // the server down.
+    let (migrations_failed, migrations_failed_rx) = oneshot::channel();
+    let migrations = (config.server.migrate && !args.skip_migrations).then(|| {
+        let db = db.clone();
+        tokio::spawn(async move {
+            let migrated = db::run_migrations_when_reachable(&db).await;
+            if let Err(error) = &migrated {
+                tracing::error!(%error, "couldn't apply migrations, shutting down");
+                let _ = migrations_failed.send(());
+            }
+            migrated
+        })
+    });
+
+    let state = ApiContext {
+        db,
+        config: Arc::new(config),
+        metrics: telemetry::install_metrics()?,
+    };
+    let cached = |route, handler| cache(handler, state.config.cache.ttl(route), BasicKeyer);
+    let cached_feed = |route, handler| {
+        cache(
+            handler,
+            state.config.cache.ttl(route),
+            routes::feed_cache_key,
+        )
+    };
 
     let router = Router::new()
+        .route("/", cached("/", get(routes::get_albums)))
         .route(
-            "/",
-            get(routes::get_albums).layer(CacheLayer::with_lifespan(Duration::from_secs(1))),
+            "/update",
+            post(routes::add_albums).layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::require_token,
+            )),
         )
-        .route("/update", post(routes::add_albums))
+        .route("/random", get(routes::get_random_albums))
+        .route("/export.csv", get(routes::export_csv))
+        .route("/export.ndjson", get(routes::export_ndjson))
+        .route(
+            "/feed.atom",
+            cached_feed("/feed.atom", get(routes::get_atom_feed)),
+        )
+        .route(
+            "/feed.rss",
+            cached_feed("/feed.rss", get(routes::get_rss_feed)),
+        )
+        .route("/gems", cached("/gems", get(routes::get_hidden_gems)))
+        .route("/calendar", cached("/calendar", get(routes::get_calendar)))
+        .route(
+            "/calendar.ics",
+            cached("/calendar.ics", get(routes::get_calendar_ical)),
+        )
+        .route(
+            "/charts/{period}",
+            cached("/charts/{period}", get(routes::get_chart)),
+        )
+        .route("/stats", cached("/stats", get(routes::get_stats)))
         .route(
             "/genre/{genre}",
-            get(routes::get_genre).layer(CacheLayer::with_lifespan(Duration::from_secs(1))),
+            cached("/genre/{genre}", get(routes::get_genre)),
         )
         .route(
             "/mood/{mood}",
-            get(routes::get_mood).layer(CacheLayer::with_lifespan(Duration::from_secs(1))),
+            cached("/mood/{mood}", get(routes::get_mood)),
+        )
+        .route(
+            "/genre/{genre}/trend",
+            cached("/genre/{genre}/trend", get(routes::get_genre_trend)),
+        )
+        .route(
+            "/mood/{mood}/trend",
+            cached("/mood/{mood}/trend", get(routes::get_mood_trend)),
+        )
+        .route(
+            "/genres/growing",
+            cached("/genres/growing", get(routes::get_growing_genres)),
+        )
+        .route(
+            "/moods/growing",
+            cached("/moods/growing", get(routes::get_growing_moods)),
+        )
+        .route(
+            "/genres/graph",
+            cached("/genres/graph", get(routes::get_genre_graph)),
+        )
+        .route(
+            "/moods/graph",
+            cached("/moods/graph", get(routes::get_mood_graph)),
+        )
+        .route(
+            "/artists/graph",
+            cached("/artists/graph", get(routes::get_artist_graph)),
         )
         .route("/artist/{artist_id}", get(routes::get_artist))
-        .with_state(ApiContext { db });
+        .route(
+            "/artist/{artist_id}/similar",
+            cached(
+                "/artist/{artist_id}/similar",
+                get(routes::get_similar_artists),
+            ),
+        )
+        .route(
+            "/album/{album_id}/similar",
+            cached("/album/{album_id}/similar", get(routes::get_similar_albums)),
+        )
+        .route("/metrics", get(telemetry::render_metrics))
+        .route("/healthz", get(routes::get_health))
+        .route("/readyz", get(routes::get_readiness))
+        .route("/ui", get(html::albums))
+        .route("/ui/album/{album_id}", get(html::album))
+        .route("/ui/genre/{genre}", get(html::genre))
+        .route("/ui/mood/{mood}", get(html::mood))
+        .route("/ui/artist/{artist_id}", get(html::artist))
+        .route_layer(middleware::from_fn(telemetry::track_requests))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .with_state(state.clone());
+
+    let listener = tokio::net::TcpListener::bind(SocketAddr::new(
+        state.config.server.bind,
+        state.config.server.port,
+    ))
+    .await?;
+    tracing::info!(address = %listener.local_addr()?, "listening");
+
+    // Stop accepting connections on a signal and let the requests in flight,
+    // /update batches above all, finish. Past the timeout the process exits
+    // from under them, and the albums they were writing roll back.
+    let (shutdown_started, shutdown_rx) = oneshot::channel();
+    let server = axum::serve(listener, router).with_graceful_shutdown(async move {
+        tokio::select! {
+            _ = shutdown_signal() => {},
+            Ok(()) = migrations_failed_rx => {},
+        }
+        tracing::info!("shutting down, waiting for requests in flight");
+        let _ = shutdown_started.send(());
+    });
+    let timeout = Duration::from_secs(state.config.server.shutdown_timeout_secs);
+    let drained = tokio::select! {
+        result = server.into_future() => {
+            result?;
+            true
+        }
+        _ = async {
+            match shutdown_rx.await {
+                Ok(()) => tokio::time::sleep(timeout).await,
+                Err(_) => std::future::pending().await,
+            }
+        } => {
+            tracing::warn!(
+                timeout_secs = timeout.as_secs(),
+                "requests still in flight after the shutdown timeout, dropping them"
+            );
+            false
+        }
+    };
+
+    let migrated = match migrations {
+        Some(migrations) if migrations.is_finished() => {
+            migrations.await.map_err(anyhow::Error::from)?
+        }
+        Some(migrations) => {
+            migrations.abort();
+            Ok(())
+        }
+        None => Ok(()),
+    };
+    // Past the timeout, connections still run in their own tasks holding on
+    // to pool connections, and closing the pool would wait for them.
+    if drained {
+        state.db.close().await;
+    }
+    tracing::info!("stopped");
+    migrated
+}
+
+/// Caches `handler`'s responses for `ttl`, by whatever `keyer` makes of the
+/// request.
+fn cache<K>(handler: MethodRouter<ApiContext>, ttl: Duration, keyer: K) -> MethodRouter<ApiContext>
+where
+    K: Keyer + Send + Sync + 'static,
+    K::Key: Debug + Hash + Eq + Clone + Send + Sync + 'static,
+{
+    handler
+        .layer(middleware::from_fn(telemetry::mark_cache_miss))
+        .layer(CacheLayer::with_lifespan_and_keyer(ttl, keyer))
+        .layer(middleware::from_fn(telemetry::track_cache))
+}
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:5000").await?;
-    axum::serve(listener, router).await?;
+/// Resolves on ctrl-c, or SIGTERM where there is such a thing.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("couldn't listen for ctrl-c");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("couldn't listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    Ok(())
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
@@ -1,17 +1,29 @@
 use axum::{
     Json,
-    extract::{Path, Query, State},
+    body::Body,
+    extract::{OriginalUri, Path, Query, State},
+    http::{HeaderMap, HeaderValue, Method, Request, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
 };
 use chrono::NaiveDate;
+use futures::StreamExt;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::{
-    ApiContext, Result, db,
-    types::{Album, GenreInfo, MoodInfo, NewAlbum},
+    ApiContext, Result,
+    config::PaginationConfig,
+    db,
+    error::AppError,
+    export, feed, graph, ical,
+    types::{
+        Album, CalendarGroup, CalendarGrouping, ChartEntry, FeedOrder, GenreInfo, GenreTrend,
+        Graph, GraphFormat, MoodInfo, MoodTrend, NewAlbum, Readiness, SimilarAlbum, SimilarArtist,
+        SimilarityMetric, Stats, TagGrowth,
+    },
 };
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Default)]
 pub struct AlbumFilter {
     #[serde(default)]
     pub page: i64,
@@ -22,12 +34,72 @@ pub struct AlbumFilter {
     pub genres: Vec<String>,
     #[serde(default, deserialize_with = "deserialize_comma_separated")]
     pub moods: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_comma_separated")]
+    pub artists: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_empty_as_none")]
     pub min_rating: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_empty_as_none")]
     pub since: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "deserialize_empty_as_none")]
     pub to: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "deserialize_empty_as_none")]
     pub url: Option<String>,
 }
 
+#[derive(Deserialize, Clone)]
+pub struct SimilarityFilter {
+    #[serde(default)]
+    pub metric: SimilarityMetric,
+    pub min_support: Option<i64>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct CalendarFilter {
+    #[serde(default)]
+    pub group_by: CalendarGrouping,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChartFilter {
+    pub min_voters: Option<i32>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct FeedFilter {
+    #[serde(default)]
+    pub order: FeedOrder,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct GemsFilter {
+    pub min_voters: Option<i32>,
+    pub max_voters: Option<i32>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct GraphFilter {
+    #[serde(default)]
+    pub format: GraphFormat,
+    pub min_weight: Option<i64>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct GrowthFilter {
+    pub previous_since: Option<NaiveDate>,
+    pub previous_to: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RandomFilter {
+    pub seed: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct SimilarAlbumsFilter {
+    pub score_weight: Option<f64>,
+    pub recency_weight: Option<f64>,
+}
+
 fn deserialize_comma_separated<'de, D>(
     deserializer: D,
 ) -> std::result::Result<Vec<String>, D::Error>
@@ -35,10 +107,27 @@ where
     D: serde::Deserializer<'de>,
 {
     let s: Option<String> = Option::deserialize(deserializer)?;
-    Ok(
-        s.map(|s| s.split(',').map(|s| s.trim().to_string()).collect())
-            .unwrap_or_default(),
-    )
+    Ok(s.map(|s| {
+        s.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    })
+    .unwrap_or_default())
+}
+
+/// Treats empty query values, like the ones left by an empty form field, as missing.
+fn deserialize_empty_as_none<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    match s.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(s) => s.parse().map(Some).map_err(serde::de::Error::custom),
+    }
 }
 
 pub async fn add_albums(
@@ -49,28 +138,321 @@ pub async fn add_albums(
         .iter()
         .map(async |album| Ok(db::register_album(&state.db, album).await?));
     let albums: Result<Vec<Album>> = futures::future::try_join_all(stream).await;
-    Ok(Json(albums?))
+    let albums = albums?;
+    tracing::info!(albums = albums.len(), "ingested albums");
+    Ok(Json(albums))
 }
 
 pub async fn get_albums(
     State(state): State<ApiContext>,
     album_filter: Query<AlbumFilter>,
 ) -> Result<Json<Vec<Album>>> {
-    let (page, limit) = get_pagination_params(album_filter.clone());
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
     Ok(Json(
         db::get_albums(&state.db, page, limit, &album_filter).await?,
     ))
 }
 
+pub async fn get_random_albums(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+    random: Query<RandomFilter>,
+) -> Result<Json<Vec<Album>>> {
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    Ok(Json(
+        db::get_random_albums(
+            &state.db,
+            page,
+            limit,
+            random.seed.as_deref(),
+            &album_filter,
+        )
+        .await?,
+    ))
+}
+
+/// Without `since` and `to`, the calendar covers the last four weeks.
+pub async fn get_calendar(
+    State(state): State<ApiContext>,
+    Query(mut album_filter): Query<AlbumFilter>,
+    calendar_filter: Query<CalendarFilter>,
+) -> Result<Json<Vec<CalendarGroup>>> {
+    let to = *album_filter
+        .to
+        .get_or_insert_with(|| chrono::Utc::now().date_naive());
+    if album_filter.since.is_none() {
+        album_filter.since = Some(days_before(to, 28)?);
+    }
+    Ok(Json(
+        db::get_calendar(&state.db, calendar_filter.group_by, &album_filter).await?,
+    ))
+}
+
+pub async fn get_calendar_ical(
+    State(state): State<ApiContext>,
+    Query(mut album_filter): Query<AlbumFilter>,
+) -> Result<Response> {
+    default_ical_window(&mut album_filter, chrono::Utc::now().date_naive())?;
+    let albums = db::get_albums_by_release(&state.db, &album_filter).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        ical::to_ical(&albums),
+    )
+        .into_response())
+}
+
+pub async fn get_chart(
+    State(state): State<ApiContext>,
+    Path(period): Path<String>,
+    album_filter: Query<AlbumFilter>,
+    chart_filter: Query<ChartFilter>,
+) -> Result<Json<Vec<ChartEntry>>> {
+    let (since, to) = parse_chart_period(&period)?;
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    Ok(Json(
+        db::get_chart(
+            &state.db,
+            since,
+            to,
+            non_negative("min_voters", chart_filter.min_voters.unwrap_or(10))?,
+            page,
+            limit,
+            &album_filter,
+        )
+        .await?,
+    ))
+}
+
+/// Turns `2024` into the whole of 2024 and `2010s` into 2010 through 2019.
+fn parse_chart_period(period: &str) -> Result<(NaiveDate, NaiveDate)> {
+    let invalid = || {
+        AppError::BadRequest(format!(
+            "{period} isn't a chart period, try a year like 2024 or a decade like 2010s"
+        ))
+    };
+    let (year, years) = match period.strip_suffix('s') {
+        Some(decade) => (decade.parse::<i32>().map_err(|_| invalid())?, 10),
+        None => (period.parse::<i32>().map_err(|_| invalid())?, 1),
+    };
+    if years == 10 && year % 10 != 0 {
+        return Err(invalid());
+    }
+    let since = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(invalid)?;
+    let last_year = year.checked_add(years - 1).ok_or_else(invalid)?;
+    let to = NaiveDate::from_ymd_opt(last_year, 12, 31).ok_or_else(invalid)?;
+    Ok((since, to))
+}
+
+/// `date` minus `days`, or a 400 when that's before the earliest date there is.
+fn days_before(date: NaiveDate, days: u64) -> Result<NaiveDate> {
+    date.checked_sub_days(chrono::Days::new(days))
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("{date} is too early to go {days} days back from"))
+        })
+}
+
+/// Calendar apps poll `.ics` feeds, so without a range of their own they get
+/// the last four weeks and the year ahead of `today` instead of every album.
+fn default_ical_window(album_filter: &mut AlbumFilter, today: NaiveDate) -> Result<()> {
+    if album_filter.since.is_none() {
+        album_filter.since = Some(days_before(today, 28)?);
+    }
+    if album_filter.to.is_none() {
+        album_filter.to = Some(
+            today
+                .checked_add_days(chrono::Days::new(52 * 7))
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!("{today} is too late to go a year ahead from"))
+                })?,
+        );
+    }
+    Ok(())
+}
+
+/// Voter counts below zero make no sense, and divide by zero in the
+/// weighted chart rating.
+fn non_negative(name: &str, value: i32) -> Result<i32> {
+    if value < 0 {
+        return Err(AppError::BadRequest(format!(
+            "{name} must be 0 or more, got {value}"
+        )));
+    }
+    Ok(value)
+}
+
+pub async fn get_health() -> &'static str {
+    "ok"
+}
+
+pub async fn get_readiness(State(state): State<ApiContext>) -> (StatusCode, Json<Readiness>) {
+    let readiness = db::get_readiness(&state.db).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+pub async fn get_stats(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Json<Stats>> {
+    Ok(Json(db::get_stats(&state.db, &album_filter).await?))
+}
+
+pub async fn export_csv(
+    State(state): State<ApiContext>,
+    Query(album_filter): Query<AlbumFilter>,
+) -> Response {
+    let rows = db::stream_albums(state.db, album_filter)
+        .map(|album| album.map(|album| export::to_csv_row(&album)));
+    let body = futures::stream::once(async { Ok(export::CSV_HEADER.to_string()) }).chain(rows);
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"albums.csv\"",
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+pub async fn export_ndjson(
+    State(state): State<ApiContext>,
+    Query(album_filter): Query<AlbumFilter>,
+) -> Response {
+    let rows = db::stream_albums(state.db, album_filter)
+        .map(|album| album.and_then(|album| export::to_ndjson_row(&album)));
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"albums.ndjson\"",
+            ),
+        ],
+        Body::from_stream(rows),
+    )
+        .into_response()
+}
+
+pub async fn get_atom_feed(
+    State(state): State<ApiContext>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    album_filter: Query<AlbumFilter>,
+    feed_filter: Query<FeedFilter>,
+) -> Result<Response> {
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    let albums =
+        db::get_recent_albums(&state.db, page, limit, feed_filter.order, &album_filter).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        feed::to_atom(
+            &albums,
+            "music-rs albums",
+            &self_url(state.config.server.public_url.as_deref(), &headers, &uri),
+        ),
+    )
+        .into_response())
+}
+
+pub async fn get_rss_feed(
+    State(state): State<ApiContext>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    album_filter: Query<AlbumFilter>,
+    feed_filter: Query<FeedFilter>,
+) -> Result<Response> {
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    let albums =
+        db::get_recent_albums(&state.db, page, limit, feed_filter.order, &album_filter).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        feed::to_rss(
+            &albums,
+            "music-rs albums",
+            &self_url(state.config.server.public_url.as_deref(), &headers, &uri),
+        ),
+    )
+        .into_response())
+}
+
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+type FeedCacheKey = (Method, Uri, Option<HeaderValue>, Option<HeaderValue>);
+
+/// Feeds link back to themselves with [`self_url`], so responses are cached
+/// per host and scheme as well as per request.
+pub fn feed_cache_key(request: &Request<Body>) -> FeedCacheKey {
+    let header = |name| request.headers().get(name).cloned();
+    (
+        request.method().clone(),
+        request.uri().clone(),
+        header(header::HOST.as_str()),
+        header(X_FORWARDED_PROTO),
+    )
+}
+
+/// Where a feed is served from: under `server.public_url` if it's set,
+/// otherwise on the `Host` the request came to, over https when a proxy in
+/// front says so with `X-Forwarded-Proto`.
+fn self_url(public_url: Option<&str>, headers: &HeaderMap, uri: &Uri) -> String {
+    if let Some(public_url) = public_url {
+        return format!("{}{uri}", public_url.trim_end_matches('/'));
+    }
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let scheme = match header(X_FORWARDED_PROTO).map(|proto| proto.split(',').next()) {
+        Some(Some(proto)) if proto.trim().eq_ignore_ascii_case("https") => "https",
+        _ => "http",
+    };
+    let host = header(header::HOST.as_str()).unwrap_or("localhost");
+    format!("{scheme}://{host}{uri}")
+}
+
+pub async fn get_hidden_gems(
+    State(state): State<ApiContext>,
+    Query(mut album_filter): Query<AlbumFilter>,
+    gems_filter: Query<GemsFilter>,
+) -> Result<Json<Vec<Album>>> {
+    album_filter.min_rating.get_or_insert(3.5);
+    let (page, limit) =
+        get_pagination_params(Query(album_filter.clone()), &state.config.pagination)?;
+    let min_voters = non_negative("min_voters", gems_filter.min_voters.unwrap_or(1))?;
+    let max_voters = non_negative("max_voters", gems_filter.max_voters.unwrap_or(100))?;
+    Ok(Json(
+        db::get_hidden_gems(
+            &state.db,
+            page,
+            limit,
+            min_voters,
+            max_voters,
+            &album_filter,
+        )
+        .await?,
+    ))
+}
+
 pub async fn get_genre(
     State(state): State<ApiContext>,
     Path(genre): Path<String>,
     album_filter: Query<AlbumFilter>,
+    similarity: Query<SimilarityFilter>,
 ) -> Result<Json<GenreInfo>> {
     let genre_id = Uuid::parse_str(&genre)?;
-    let (page, limit) = get_pagination_params(album_filter);
+    let (page, limit) = get_pagination_params(album_filter, &state.config.pagination)?;
     let db_genre = db::get_genre(&state.db, genre_id).await?;
-    let db_similar_genres = db::get_similar_genres(&state.db, genre_id).await?;
+    let db_similar_genres = db::get_similar_genres(
+        &state.db,
+        genre_id,
+        similarity.metric,
+        similarity.min_support.unwrap_or(1),
+    )
+    .await?;
     let db_genre_albums = db::get_albums_for_genre(&state.db, genre_id, page, limit).await?;
 
     Ok(Json(GenreInfo {
@@ -84,11 +466,18 @@ pub async fn get_mood(
     State(state): State<ApiContext>,
     Path(mood): Path<String>,
     album_filter: Query<AlbumFilter>,
+    similarity: Query<SimilarityFilter>,
 ) -> Result<Json<MoodInfo>> {
     let mood_id = Uuid::parse_str(&mood)?;
-    let (page, limit) = get_pagination_params(album_filter);
+    let (page, limit) = get_pagination_params(album_filter, &state.config.pagination)?;
     let db_mood = db::get_mood(&state.db, mood_id).await?;
-    let db_similar_moods = db::get_similar_moods(&state.db, mood_id).await?;
+    let db_similar_moods = db::get_similar_moods(
+        &state.db,
+        mood_id,
+        similarity.metric,
+        similarity.min_support.unwrap_or(1),
+    )
+    .await?;
     let db_mood_albums = db::get_albums_for_mood(&state.db, mood_id, page, limit).await?;
     Ok(Json(MoodInfo {
         mood: db_mood,
@@ -97,28 +486,390 @@ pub async fn get_mood(
     }))
 }
 
+pub async fn get_genre_trend(
+    State(state): State<ApiContext>,
+    Path(genre): Path<String>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Json<GenreTrend>> {
+    let genre_id = Uuid::parse_str(&genre)?;
+    Ok(Json(
+        db::get_genre_trend(&state.db, genre_id, &album_filter).await?,
+    ))
+}
+
+pub async fn get_mood_trend(
+    State(state): State<ApiContext>,
+    Path(mood): Path<String>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Json<MoodTrend>> {
+    let mood_id = Uuid::parse_str(&mood)?;
+    Ok(Json(
+        db::get_mood_trend(&state.db, mood_id, &album_filter).await?,
+    ))
+}
+
+pub async fn get_growing_genres(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+    growth: Query<GrowthFilter>,
+) -> Result<Json<Vec<TagGrowth>>> {
+    let (previous, current) = get_growth_periods(&album_filter, &growth)?;
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    Ok(Json(
+        db::get_growing_genres(&state.db, previous, current, page, limit, &album_filter).await?,
+    ))
+}
+
+pub async fn get_growing_moods(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+    growth: Query<GrowthFilter>,
+) -> Result<Json<Vec<TagGrowth>>> {
+    let (previous, current) = get_growth_periods(&album_filter, &growth)?;
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    Ok(Json(
+        db::get_growing_moods(&state.db, previous, current, page, limit, &album_filter).await?,
+    ))
+}
+
+/// The current period is `since..to`, defaulting to the last year. The
+/// previous one defaults to an equally long period right before it.
+fn get_growth_periods(
+    album_filter: &AlbumFilter,
+    growth: &GrowthFilter,
+) -> Result<((NaiveDate, NaiveDate), (NaiveDate, NaiveDate))> {
+    let to = album_filter
+        .to
+        .unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let since = match album_filter.since {
+        Some(since) => since,
+        None => days_before(to, 365)?,
+    };
+    if since > to {
+        return Err(AppError::BadRequest(format!(
+            "since ({since}) must not be after to ({to})"
+        )));
+    }
+    let previous_to = match growth.previous_to {
+        Some(previous_to) => previous_to,
+        None => days_before(since, 1)?,
+    };
+    let previous_since = match growth.previous_since {
+        Some(previous_since) => previous_since,
+        None => previous_to.checked_sub_signed(to - since).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "{previous_to} is too early to fit a period as long as {since} to {to} before it"
+            ))
+        })?,
+    };
+    if previous_since > previous_to {
+        return Err(AppError::BadRequest(format!(
+            "previous_since ({previous_since}) must not be after previous_to ({previous_to})"
+        )));
+    }
+    Ok(((previous_since, previous_to), (since, to)))
+}
+
+pub async fn get_genre_graph(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+    graph_filter: Query<GraphFilter>,
+) -> Result<Response> {
+    let graph = db::get_genre_graph(
+        &state.db,
+        graph_filter.min_weight.unwrap_or(1),
+        &album_filter,
+    )
+    .await?;
+    Ok(graph_response(&graph, graph_filter.format, "genres"))
+}
+
+pub async fn get_mood_graph(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+    graph_filter: Query<GraphFilter>,
+) -> Result<Response> {
+    let graph = db::get_mood_graph(
+        &state.db,
+        graph_filter.min_weight.unwrap_or(1),
+        &album_filter,
+    )
+    .await?;
+    Ok(graph_response(&graph, graph_filter.format, "moods"))
+}
+
+pub async fn get_artist_graph(
+    State(state): State<ApiContext>,
+    album_filter: Query<AlbumFilter>,
+    graph_filter: Query<GraphFilter>,
+) -> Result<Response> {
+    let graph = db::get_artist_graph(
+        &state.db,
+        graph_filter.min_weight.unwrap_or(1),
+        &album_filter,
+    )
+    .await?;
+    Ok(graph_response(&graph, graph_filter.format, "artists"))
+}
+
+fn graph_response(graph: &Graph, format: GraphFormat, name: &str) -> Response {
+    match format {
+        GraphFormat::Json => Json(graph).into_response(),
+        GraphFormat::Dot => (
+            [(header::CONTENT_TYPE, "text/vnd.graphviz; charset=utf-8")],
+            graph::to_dot(graph, name),
+        )
+            .into_response(),
+        GraphFormat::GraphMl => (
+            [(
+                header::CONTENT_TYPE,
+                "application/graphml+xml; charset=utf-8",
+            )],
+            graph::to_graphml(graph, name),
+        )
+            .into_response(),
+    }
+}
+
 pub async fn get_artist(
     State(state): State<ApiContext>,
     Path(artist_id): Path<String>,
     album_filter: Query<AlbumFilter>,
 ) -> Result<Json<Vec<Album>>> {
     let id = Uuid::parse_str(&artist_id)?;
-    let (page, limit) = get_pagination_params(album_filter);
+    let (page, limit) = get_pagination_params(album_filter, &state.config.pagination)?;
     Ok(Json(
         db::get_albums_for_artist(&state.db, id, page, limit).await?,
     ))
 }
 
-fn get_pagination_params(album_filter: Query<AlbumFilter>) -> (i64, i64) {
+pub async fn get_similar_artists(
+    State(state): State<ApiContext>,
+    Path(artist_id): Path<String>,
+    album_filter: Query<AlbumFilter>,
+) -> Result<Json<Vec<SimilarArtist>>> {
+    let id = Uuid::parse_str(&artist_id)?;
+    let (page, limit) = get_pagination_params(album_filter, &state.config.pagination)?;
+    Ok(Json(
+        db::get_similar_artists(&state.db, id, page, limit).await?,
+    ))
+}
+
+pub async fn get_similar_albums(
+    State(state): State<ApiContext>,
+    Path(album_id): Path<String>,
+    album_filter: Query<AlbumFilter>,
+    weights: Query<SimilarAlbumsFilter>,
+) -> Result<Json<Vec<SimilarAlbum>>> {
+    let id = Uuid::parse_str(&album_id)?;
+    let (page, limit) = get_pagination_params(album_filter.clone(), &state.config.pagination)?;
+    Ok(Json(
+        db::get_similar_albums(&state.db, id, page, limit, &album_filter, &weights).await?,
+    ))
+}
+
+/// The requested page and page size, with an unset (or zero) `page` or
+/// `limit` falling back to the first page and the configured default.
+pub fn get_pagination_params(
+    album_filter: Query<AlbumFilter>,
+    pagination: &PaginationConfig,
+) -> Result<(i64, i64)> {
     let limit = if album_filter.limit == 0 {
-        25
+        pagination.default_limit
     } else {
         album_filter.limit
     };
+    if !(1..=pagination.max_limit).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}, got {limit}",
+            pagination.max_limit
+        )));
+    }
     let page = if album_filter.page == 0 {
         1
     } else {
         album_filter.page
     };
-    (page, limit)
+    // Any further and the offset, (page - 1) * limit, no longer fits in a
+    // bigint.
+    let max_page = i64::MAX / limit;
+    if !(1..=max_page).contains(&page) {
+        return Err(AppError::BadRequest(format!(
+            "page must be between 1 and {max_page}, got {page}"
+        )));
+    }
+    Ok((page, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderName;
+
+    use super::*;
+
+    fn pagination(page: i64, limit: i64) -> Result<(i64, i64)> {
+        get_pagination_params(
+            Query(AlbumFilter {
+                page,
+                limit,
+                ..Default::default()
+            }),
+            &PaginationConfig::default(),
+        )
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn chart_periods() {
+        assert_eq!(
+            parse_chart_period("2024").unwrap(),
+            (date(2024, 1, 1), date(2024, 12, 31))
+        );
+        assert_eq!(
+            parse_chart_period("2010s").unwrap(),
+            (date(2010, 1, 1), date(2019, 12, 31))
+        );
+        assert!(parse_chart_period("2015s").is_err());
+        assert!(parse_chart_period("nineties").is_err());
+        assert!(parse_chart_period(&i32::MAX.to_string()).is_err());
+        assert!(parse_chart_period("2147483640s").is_err());
+    }
+
+    #[test]
+    fn days_before_stays_in_range() {
+        assert_eq!(days_before(date(2024, 3, 1), 28).unwrap(), date(2024, 2, 2));
+        assert!(days_before(NaiveDate::MIN, 28).is_err());
+    }
+
+    #[test]
+    fn ical_window() {
+        let window = |since, to| {
+            let mut album_filter = AlbumFilter {
+                since,
+                to,
+                ..Default::default()
+            };
+            default_ical_window(&mut album_filter, date(2024, 3, 1))
+                .map(|()| (album_filter.since, album_filter.to))
+        };
+        assert_eq!(
+            window(None, None).unwrap(),
+            (Some(date(2024, 2, 2)), Some(date(2025, 2, 28)))
+        );
+        assert_eq!(
+            window(Some(date(2020, 1, 1)), None).unwrap(),
+            (Some(date(2020, 1, 1)), Some(date(2025, 2, 28)))
+        );
+        assert_eq!(
+            window(None, Some(date(2024, 12, 31))).unwrap(),
+            (Some(date(2024, 2, 2)), Some(date(2024, 12, 31)))
+        );
+    }
+
+    fn growth_periods(
+        since: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<((NaiveDate, NaiveDate), (NaiveDate, NaiveDate))> {
+        get_growth_periods(
+            &AlbumFilter {
+                since,
+                to,
+                ..Default::default()
+            },
+            &GrowthFilter {
+                previous_since: None,
+                previous_to: None,
+            },
+        )
+    }
+
+    #[test]
+    fn growth_periods_default_to_the_year_before() {
+        assert_eq!(
+            growth_periods(None, Some(date(2024, 12, 31))).unwrap(),
+            (
+                (date(2022, 12, 31), date(2023, 12, 31)),
+                (date(2024, 1, 1), date(2024, 12, 31))
+            )
+        );
+    }
+
+    #[test]
+    fn growth_periods_stay_in_range() {
+        assert!(growth_periods(Some(NaiveDate::MIN), None).is_err());
+        assert!(growth_periods(None, Some(NaiveDate::MIN)).is_err());
+        assert!(growth_periods(Some(date(2024, 6, 1)), Some(date(2024, 1, 1))).is_err());
+        assert!(growth_periods(Some(date(-262140, 1, 1)), Some(date(2024, 1, 1))).is_err());
+    }
+
+    #[test]
+    fn voter_counts_are_not_negative() {
+        assert_eq!(non_negative("min_voters", 0).unwrap(), 0);
+        assert!(non_negative("min_voters", -5).is_err());
+    }
+
+    fn feed_url(public_url: Option<&str>, headers: &[(&'static str, &str)]) -> String {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (HeaderName::from_static(name), value.parse().unwrap()))
+            .collect();
+        self_url(
+            public_url,
+            &headers,
+            &"/feed.atom?genres=shoegaze".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn feed_self_urls() {
+        assert_eq!(
+            feed_url(None, &[]),
+            "http://localhost/feed.atom?genres=shoegaze"
+        );
+        assert_eq!(
+            feed_url(None, &[("host", "music.example.com")]),
+            "http://music.example.com/feed.atom?genres=shoegaze"
+        );
+        assert_eq!(
+            feed_url(
+                None,
+                &[
+                    ("host", "music.example.com"),
+                    ("x-forwarded-proto", "https, http")
+                ]
+            ),
+            "https://music.example.com/feed.atom?genres=shoegaze"
+        );
+        assert_eq!(
+            feed_url(
+                Some("https://example.com/music/"),
+                &[("host", "localhost:5000"), ("x-forwarded-proto", "http")]
+            ),
+            "https://example.com/music/feed.atom?genres=shoegaze"
+        );
+    }
+
+    #[test]
+    fn pagination_defaults() {
+        assert_eq!(pagination(0, 0).unwrap(), (1, 25));
+    }
+
+    #[test]
+    fn pagination_limit_bounds() {
+        assert_eq!(pagination(1, 1).unwrap(), (1, 1));
+        assert_eq!(pagination(1, 500).unwrap(), (1, 500));
+        assert!(pagination(1, -1).is_err());
+        assert!(pagination(1, 501).is_err());
+    }
+
+    #[test]
+    fn pagination_page_bounds() {
+        assert_eq!(pagination(i64::MAX, 1).unwrap(), (i64::MAX, 1));
+        assert!(pagination(-1, 1).is_err());
+        assert_eq!(pagination(i64::MAX / 500, 500).unwrap().0, i64::MAX / 500);
+        assert!(pagination(i64::MAX / 500 + 1, 500).is_err());
+    }
 }
@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use sqlx::PgPool;
+
+use crate::{Result, db, types::Snapshot};
+
+/// Gzip streams start with these two bytes.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Writes the whole catalogue to `path`, gzipped if it ends in `.gz`.
+pub async fn export_snapshot(db: &PgPool, path: &Path) -> Result<Snapshot> {
+    let snapshot = db::get_snapshot(db).await?;
+    let gzip = path.extension().is_some_and(|extension| extension == "gz");
+    // Serializing and compressing the whole catalogue takes a while, keep it
+    // off the async workers.
+    let (snapshot, contents) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let contents = if gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            serde_json::to_writer(&mut encoder, &snapshot)?;
+            encoder.finish()?
+        } else {
+            serde_json::to_vec_pretty(&snapshot)?
+        };
+        Ok((snapshot, contents))
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
+    tokio::fs::write(path, contents).await?;
+    Ok(snapshot)
+}
+
+/// Restores a snapshot written by [`export_snapshot`], gzipped or not.
+pub async fn import_snapshot(db: &PgPool, path: &Path) -> Result<Snapshot> {
+    let contents = tokio::fs::read(path).await?;
+    let snapshot = tokio::task::spawn_blocking(move || -> Result<Snapshot> {
+        Ok(if contents.starts_with(&GZIP_MAGIC) {
+            serde_json::from_reader(GzDecoder::new(contents.as_slice()))?
+        } else {
+            serde_json::from_slice(&contents)?
+        })
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
+    db::restore_snapshot(db, &snapshot).await?;
+    Ok(snapshot)
+}
+
+fn describe(snapshot: &Snapshot) -> String {
+    format!(
+        "{} albums, {} artists, {} genres, {} moods and {} tracks",
+        snapshot.albums.len(),
+        snapshot.artists.len(),
+        snapshot.genres.len(),
+        snapshot.moods.len(),
+        snapshot.tracks.len()
+    )
+}
+
+pub async fn run_export(db: &PgPool, path: &Path) -> Result<()> {
+    let snapshot = export_snapshot(db, path).await?;
+    println!("wrote {} to {}", describe(&snapshot), path.display());
+    Ok(())
+}
+
+pub async fn run_import(db: &PgPool, path: &Path) -> Result<()> {
+    let snapshot = import_snapshot(db, path).await?;
+    println!("restored {} from {}", describe(&snapshot), path.display());
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::{
+    io::IsTerminal,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{self, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tracing::Span;
+use tracing_subscriber::{EnvFilter, fmt};
+
+use crate::{
+    ApiContext, Result,
+    config::{LogConfig, LogFormat},
+};
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Sends logs to stderr, so they stay out of what the subcommands print.
+/// `RUST_LOG` takes precedence over the configured filter.
+pub fn init(config: &LogConfig) -> Result<()> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.filter))
+        .map_err(|e| anyhow::anyhow!("invalid log filter: {e}"))?;
+    let subscriber = fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    match config.format {
+        LogFormat::Pretty => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+    Ok(())
+}
+
+/// The span every request is handled in, identified by its `x-request-id`
+/// and the route it matched.
+pub fn request_span<B>(request: &http::Request<B>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str);
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok());
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        route,
+        request_id,
+    )
+}
+
+/// Records metrics from here on, for `/metrics` to render.
+pub fn install_metrics() -> Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )
+        .map_err(|e| anyhow::anyhow!("can't set up metrics: {e}"))?
+        .install_recorder()
+        .map_err(|e| anyhow::anyhow!("can't set up metrics: {e}"))?;
+    Ok(handle)
+}
+
+fn route(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default()
+}
+
+/// Counts requests and how long they took, per route and status.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let labels = [
+        ("method", request.method().to_string()),
+        ("route", route(&request)),
+    ];
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let mut labels = labels.to_vec();
+    labels.push(("status", response.status().as_u16().to_string()));
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels)
+        .record(start.elapsed().as_secs_f64());
+    response
+}
+
+/// Set by [`mark_cache_miss`] when the request made it past the cache.
+#[derive(Clone, Default)]
+struct CacheMiss(Arc<AtomicBool>);
+
+/// Goes outside a `CacheLayer` and counts each request as a hit or a miss,
+/// depending on whether [`mark_cache_miss`] inside it got to see it.
+pub async fn track_cache(mut request: Request, next: Next) -> Response {
+    let route = route(&request);
+    let miss = CacheMiss::default();
+    request.extensions_mut().insert(miss.clone());
+    let response = next.run(request).await;
+    let result = if miss.0.load(Ordering::Relaxed) {
+        "miss"
+    } else {
+        "hit"
+    };
+    metrics::counter!("http_cache_requests_total", "route" => route, "result" => result)
+        .increment(1);
+    response
+}
+
+pub async fn mark_cache_miss(request: Request, next: Next) -> Response {
+    if let Some(miss) = request.extensions().get::<CacheMiss>() {
+        miss.0.store(true, Ordering::Relaxed);
+    }
+    next.run(request).await
+}
+
+pub async fn render_metrics(State(state): State<ApiContext>) -> impl IntoResponse {
+    metrics::gauge!("db_pool_connections", "state" => "idle").set(state.db.num_idle() as f64);
+    metrics::gauge!("db_pool_connections", "state" => "active")
+        .set(state.db.size() as f64 - state.db.num_idle() as f64);
+    metrics::gauge!("db_pool_max_connections").set(state.db.options().get_max_connections());
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
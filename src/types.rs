@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use sqlx::types::chrono::NaiveDate;
+use sqlx::types::chrono::{DateTime, NaiveDate, Utc};
 use uuid::Uuid;
 
 #[derive(sqlx::Type, Serialize, Deserialize, Debug)]
@@ -20,6 +20,25 @@ pub struct Album {
     #[serde(with = "my_date_format")]
     pub modified_date: NaiveDate,
 }
+
+impl Album {
+    /// The album title prefixed with its artists, like `Artist, Other - Title`.
+    pub fn credited_title(&self) -> String {
+        let artists = self
+            .artists
+            .iter()
+            .flatten()
+            .map(|artist| artist.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if artists.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{artists} - {}", self.title)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NewAlbum {
     pub album: String,
@@ -98,6 +117,7 @@ pub struct SimilarGenre {
     pub id: Uuid,
     pub name: Option<String>,
     pub count: Option<i64>,
+    pub score: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -105,6 +125,179 @@ pub struct SimilarMood {
     pub id: Uuid,
     pub name: Option<String>,
     pub count: Option<i64>,
+    pub score: Option<f64>,
+}
+
+/// How related genres/moods are ranked against the one being looked at.
+///
+/// `count` is the raw number of albums sharing both tags, the others
+/// normalize it by how common each tag is on its own.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SimilarityMetric {
+    Count,
+    #[default]
+    Jaccard,
+    Pmi,
+    Lift,
+}
+
+impl SimilarityMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SimilarityMetric::Count => "count",
+            SimilarityMetric::Jaccard => "jaccard",
+            SimilarityMetric::Pmi => "pmi",
+            SimilarityMetric::Lift => "lift",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SimilarAlbum {
+    #[serde(flatten)]
+    pub album: Album,
+    pub similarity: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SimilarArtist {
+    pub id: Uuid,
+    pub name: String,
+    pub localized_name: Option<String>,
+    pub similarity: f64,
+    pub shared_releases: i64,
+}
+
+/// Nodes and links, serialized in the node/link layout d3 and networkx read.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub links: Vec<GraphLink>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct GraphNode {
+    pub id: Uuid,
+    pub name: String,
+    pub size: i64,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct GraphLink {
+    pub source: Uuid,
+    pub target: Uuid,
+    pub weight: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    Dot,
+    GraphMl,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedOrder {
+    /// Most recently added or updated by the scraper first.
+    #[default]
+    Modified,
+    /// Most recently added first, going by the time in the uuidv7 id.
+    Created,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CalendarGroup {
+    pub period: String,
+    #[serde(with = "my_date_format")]
+    pub start: NaiveDate,
+    pub count: i64,
+    pub albums: Vec<Album>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CalendarGrouping {
+    #[default]
+    Week,
+    Day,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChartEntry {
+    pub rank: i64,
+    pub weighted_score: f64,
+    #[serde(flatten)]
+    pub album: Album,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Stats {
+    pub albums: i64,
+    pub average_score: Option<f64>,
+    pub per_year: Vec<PeriodStats>,
+    pub per_month: Vec<PeriodStats>,
+    pub per_genre: Vec<TagStats>,
+    pub per_mood: Vec<TagStats>,
+    pub score_distribution: Vec<ScoreBucket>,
+    pub voter_percentiles: VoterPercentiles,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct PeriodStats {
+    pub period: String,
+    pub albums: i64,
+    pub average_score: Option<f64>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct TagStats {
+    pub id: Uuid,
+    pub name: String,
+    pub albums: i64,
+    pub average_score: Option<f64>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct ScoreBucket {
+    pub score: f64,
+    pub albums: i64,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct VoterPercentiles {
+    pub p10: Option<f64>,
+    pub p25: Option<f64>,
+    pub p50: Option<f64>,
+    pub p75: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GenreTrend {
+    pub genre: Genre,
+    pub months: Vec<PeriodStats>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MoodTrend {
+    pub mood: Mood,
+    pub months: Vec<PeriodStats>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct TagGrowth {
+    pub id: Uuid,
+    pub name: String,
+    pub previous_albums: i64,
+    pub albums: i64,
+    /// Relative change from the previous period, missing when the tag is new.
+    #[sqlx(skip)]
+    pub growth: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -121,6 +314,104 @@ pub struct MoodInfo {
     pub albums: Vec<Album>,
 }
 
+/// Every row of the catalogue, ids included, as written by `export-snapshot`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+    pub format: String,
+    pub version: u32,
+    pub created: DateTime<Utc>,
+    pub artists: Vec<Artist>,
+    pub genres: Vec<Genre>,
+    pub moods: Vec<Mood>,
+    pub albums: Vec<SnapshotAlbum>,
+    pub album_artists: Vec<AlbumArtist>,
+    pub album_genres: Vec<AlbumGenre>,
+    pub album_moods: Vec<AlbumMood>,
+    pub tracks: Vec<SnapshotTrack>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotAlbum {
+    pub id: Uuid,
+    pub title: String,
+    pub localized_title: Option<String>,
+    #[serde(with = "my_date_format")]
+    pub date: NaiveDate,
+    pub url: String,
+    pub rym_url: Option<String>,
+    pub score: f32,
+    pub voters: i32,
+    #[serde(with = "my_date_format")]
+    pub modified_date: NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotTrack {
+    pub id: Uuid,
+    pub album_id: Option<Uuid>,
+    pub track_number: i32,
+    pub title: String,
+    pub localized_title: Option<String>,
+    pub artist: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AlbumArtist {
+    pub album_id: Uuid,
+    pub artist_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AlbumGenre {
+    pub album_id: Uuid,
+    pub genre_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AlbumMood {
+    pub album_id: Uuid,
+    pub mood_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file has been edited since.
+    Changed,
+    /// Applied, but not among the migrations this binary was built with.
+    Unknown,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Changed => "changed",
+            MigrationState::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Readiness {
+    pub ready: bool,
+    /// Why the database couldn't be reached, if it couldn't.
+    pub database_error: Option<String>,
+    pub pending_migrations: Vec<i64>,
+    /// Applied, but edited since.
+    pub changed_migrations: Vec<i64>,
+}
+
 mod my_date_format {
     use anyhow::Result;
     use serde::{self, Deserialize, Deserializer, Serializer};
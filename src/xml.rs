@@ -0,0 +1,21 @@
+/// Escapes `s` for use in XML text or attribute values.
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            escape_xml(r#"<a href="?x=1&y='2'">"#),
+            "&lt;a href=&quot;?x=1&amp;y=&apos;2&apos;&quot;&gt;"
+        );
+    }
+}